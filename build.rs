@@ -0,0 +1,70 @@
+// Compiles GRnvS's libraw for the grnvs crate
+//
+// Copyright © 2023 Josef Schönberger
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included
+// in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES
+// OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.
+// IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT,
+// TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
+// OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use std::env;
+use std::path::PathBuf;
+
+fn main() {
+    // The pure Rust backend does not need libraw at all.
+    if env::var_os("CARGO_FEATURE_PURE_RUST").is_some() {
+        return;
+    }
+
+    // libraw is expected next to Cargo.toml unless GRNVS_LIBRAW_DIR says otherwise.
+    println!("cargo:rerun-if-env-changed=GRNVS_LIBRAW_DIR");
+    let libraw = env::var_os("GRNVS_LIBRAW_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            PathBuf::from(env::var_os("CARGO_MANIFEST_DIR").unwrap()).join("libraw")
+        });
+    let src = libraw.join("src");
+    let include = libraw.join("include");
+    println!("cargo:rerun-if-changed={}", src.display());
+    println!("cargo:rerun-if-changed={}", include.display());
+
+    let mut sources: Vec<PathBuf> = std::fs::read_dir(&src)
+        .unwrap_or_else(|err| {
+            panic!(
+                "Cannot read libraw's sources in {} ({}). Copy libraw into your project, set \
+                 GRNVS_LIBRAW_DIR, or enable the \"pure-rust\" feature.",
+                src.display(),
+                err
+            )
+        })
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "c"))
+        .collect();
+    sources.sort();
+
+    // Same flags as the Makefile used to pass to gcc. `compile` emits
+    // `cargo:rustc-link-lib=static=raw` and the matching search path.
+    cc::Build::new()
+        .files(&sources)
+        .include(&include)
+        .debug(true)
+        .opt_level(2)
+        .pic(true)
+        .warnings(true)
+        .extra_warnings(true)
+        .flag("-fno-strict-aliasing")
+        .compile("raw");
+}
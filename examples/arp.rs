@@ -0,0 +1,107 @@
+// Asks for the hardware address of an IPv4 address on the local network (ARP)
+//
+// Copyright © 2023 Josef Schönberger
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included
+// in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES
+// OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.
+// IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT,
+// TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
+// OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use std::env;
+use std::net::Ipv4Addr;
+use std::process::exit;
+use std::time::{Duration, Instant};
+
+use grnvs::{Layer, MacAddr, ReadOutcome, Socket};
+
+const ETHERTYPE_ARP: [u8; 2] = [0x08, 0x06];
+const ARP_REQUEST: u16 = 1;
+const ARP_REPLY: u16 = 2;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let (ifname, target) = match &args[..] {
+        [_, ifname, target] => match target.parse::<Ipv4Addr>() {
+            Ok(target) => (ifname, target),
+            Err(err) => {
+                eprintln!("Invalid IPv4 address {:?}: {}", target, err);
+                exit(2);
+            }
+        },
+        _ => {
+            eprintln!("Usage: {} <interface> <IPv4 address>", args[0]);
+            exit(2);
+        }
+    };
+    let mut socket = Socket::open(ifname, Layer::SOCK_RAW).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        exit(1);
+    });
+    let hwaddr = socket.get_hwaddr().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        exit(1);
+    });
+    let ipaddr = socket.get_ipaddr();
+
+    let mut frame = Vec::with_capacity(42);
+    frame.extend_from_slice(&MacAddr::BROADCAST.octets());
+    frame.extend_from_slice(&hwaddr.octets());
+    frame.extend_from_slice(&ETHERTYPE_ARP);
+    frame.extend_from_slice(&[0, 1, 0x08, 0x00, 6, 4]); // Ethernet/IPv4, address lengths
+    frame.extend_from_slice(&ARP_REQUEST.to_be_bytes());
+    frame.extend_from_slice(&hwaddr.octets());
+    frame.extend_from_slice(&ipaddr.octets());
+    frame.extend_from_slice(&[0; 6]);
+    frame.extend_from_slice(&target.octets());
+
+    if let Err(err) = socket.write(&frame) {
+        eprintln!("{}", err);
+        exit(1);
+    }
+
+    let deadline = Instant::now() + Duration::from_secs(2);
+    let mut buf = [0u8; 1514];
+    loop {
+        match socket.read_deadline(&mut buf, deadline) {
+            Ok(ReadOutcome::Frame(len)) => {
+                if let Some(answer) = parse_arp_reply(&buf[..len], target) {
+                    println!("{} is at {}", target, answer);
+                    return;
+                }
+            }
+            Ok(ReadOutcome::TimedOut) => {
+                eprintln!("No reply from {}", target);
+                exit(1);
+            }
+            Err(err) => {
+                eprintln!("{}", err);
+                exit(1);
+            }
+        }
+    }
+}
+
+// Returns the sender hardware address of an ARP reply from `target`.
+fn parse_arp_reply(frame: &[u8], target: Ipv4Addr) -> Option<MacAddr> {
+    if frame.len() < 42 || frame[12..14] != ETHERTYPE_ARP {
+        return None;
+    }
+    let arp = &frame[14..42];
+    if arp[6..8] != ARP_REPLY.to_be_bytes() || arp[14..18] != target.octets() {
+        return None;
+    }
+    <[u8; 6]>::try_from(&arp[8..14]).ok().map(MacAddr)
+}
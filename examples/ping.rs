@@ -0,0 +1,115 @@
+// Pings all nodes on a link (ff02::1) and lists everyone who answers
+//
+// Copyright © 2023 Josef Schönberger
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included
+// in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES
+// OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.
+// IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT,
+// TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
+// OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use std::env;
+use std::net::Ipv6Addr;
+use std::process::exit;
+use std::time::{Duration, Instant};
+
+use grnvs::{icmpv6_checksum, verify_icmpv6_checksum, Layer, ReadOutcome, Socket};
+
+const ALL_NODES: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+const ETHERTYPE_IPV6: [u8; 2] = [0x86, 0xdd];
+const IPPROTO_ICMPV6: u8 = 58;
+const ECHO_REQUEST: u8 = 128;
+const ECHO_REPLY: u8 = 129;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let [_, ifname] = &args[..] else {
+        eprintln!("Usage: {} <interface>", args[0]);
+        exit(2);
+    };
+    let mut socket = Socket::open(ifname, Layer::SOCK_RAW).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        exit(1);
+    });
+    let hwaddr = socket.get_hwaddr().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        exit(1);
+    });
+    let src = socket.get_ip6addr();
+    let identifier = std::process::id() as u16;
+
+    let mut icmp = vec![ECHO_REQUEST, 0, 0, 0];
+    icmp.extend_from_slice(&identifier.to_be_bytes());
+    icmp.extend_from_slice(&1u16.to_be_bytes()); // sequence number
+    icmp.extend_from_slice(b"grnvs ping");
+    let checksum = icmpv6_checksum(src, ALL_NODES, &icmp);
+    icmp[2..4].copy_from_slice(&checksum.to_be_bytes());
+
+    let mut frame = Vec::with_capacity(14 + 40 + icmp.len());
+    frame.extend_from_slice(&[0x33, 0x33, 0, 0, 0, 1]); // multicast MAC address of ff02::1
+    frame.extend_from_slice(&hwaddr.octets());
+    frame.extend_from_slice(&ETHERTYPE_IPV6);
+    frame.extend_from_slice(&[0x60, 0, 0, 0]); // version, traffic class, flow label
+    frame.extend_from_slice(&(icmp.len() as u16).to_be_bytes());
+    frame.extend_from_slice(&[IPPROTO_ICMPV6, 255]); // next header, hop limit
+    frame.extend_from_slice(&src.octets());
+    frame.extend_from_slice(&ALL_NODES.octets());
+    frame.extend_from_slice(&icmp);
+
+    if let Err(err) = socket.write(&frame) {
+        eprintln!("{}", err);
+        exit(1);
+    }
+    let sent = Instant::now();
+
+    // Collect replies for two seconds, ignoring all other traffic.
+    let deadline = sent + Duration::from_secs(2);
+    let mut buf = [0u8; 1514];
+    loop {
+        match socket.read_deadline(&mut buf, deadline) {
+            Ok(ReadOutcome::Frame(len)) => {
+                if let Some(from) = parse_echo_reply(&buf[..len], identifier) {
+                    println!("Reply from {}: time={:.2?}", from, sent.elapsed());
+                }
+            }
+            Ok(ReadOutcome::TimedOut) => break,
+            Err(err) => {
+                eprintln!("{}", err);
+                exit(1);
+            }
+        }
+    }
+}
+
+// Returns the sender of an ICMPv6 echo reply to our request.
+fn parse_echo_reply(frame: &[u8], identifier: u16) -> Option<Ipv6Addr> {
+    if frame.len() < 14 + 40 + 8 || frame[12..14] != ETHERTYPE_IPV6 {
+        return None;
+    }
+    let ip = &frame[14..];
+    let payload_len = u16::from_be_bytes([ip[4], ip[5]]) as usize;
+    let icmp = ip.get(40..40 + payload_len)?;
+    // The payload length may still be too short for an echo header.
+    if ip[6] != IPPROTO_ICMPV6
+        || icmp.len() < 8
+        || icmp[0] != ECHO_REPLY
+        || icmp[4..6] != identifier.to_be_bytes()
+    {
+        return None;
+    }
+    let src = Ipv6Addr::from(<[u8; 16]>::try_from(&ip[8..24]).unwrap());
+    let dst = Ipv6Addr::from(<[u8; 16]>::try_from(&ip[24..40]).unwrap());
+    verify_icmpv6_checksum(src, dst, icmp).then_some(src)
+}
@@ -0,0 +1,148 @@
+// Async packet sockets: a tokio wrapper around raw.rs's Socket
+//
+// Copyright © 2023 Josef Schönberger
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included
+// in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES
+// OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.
+// IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT,
+// TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
+// OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures_core::Stream;
+use tokio::io::unix::AsyncFd;
+
+use crate::raw::{ReadOutcome, Socket};
+
+/// A [`Socket`] driven by the tokio reactor instead of blocking the calling thread.
+///
+/// Errors are reported as [`io::Error`]s, like everywhere else in tokio; they wrap the
+/// [`Error`](crate::Error) the socket returned.
+pub struct AsyncSocket {
+    inner: AsyncFd<Socket>,
+}
+
+impl AsyncSocket {
+    /// Switches `socket` to non-blocking mode and registers it with the current tokio runtime.
+    ///
+    /// Panics if called outside of a tokio runtime, or if its IO driver is disabled.
+    pub fn new(socket: Socket) -> io::Result<Self> {
+        socket.set_nonblocking(true)?;
+        Ok(AsyncSocket {
+            inner: AsyncFd::new(socket)?,
+        })
+    }
+
+    /// Waits for a frame and writes it into `destination`.
+    ///
+    /// Returns the length of the frame. Like with [`Socket::read`], longer frames are truncated.
+    pub async fn recv(&self, destination: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let mut guard = self.inner.readable().await?;
+            match guard.try_io(|inner| recv_nonblocking(inner.get_ref(), destination)) {
+                Ok(result) => return result,
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    /// Waits until the socket can take `frame` and sends it.
+    ///
+    /// Returns the amount of bytes that were actually written.
+    pub async fn send(&self, frame: &[u8]) -> io::Result<usize> {
+        loop {
+            let mut guard = self.inner.writable().await?;
+            match guard.try_io(|inner| Ok(inner.get_ref().write_shared(frame)?)) {
+                Ok(result) => return result,
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    /// Returns a [`Stream`] of the received frames, each truncated to at most `max_len` bytes.
+    ///
+    /// The stream never ends by itself; errors are yielded and it can be polled again afterwards.
+    #[inline]
+    pub fn frames(&self, max_len: usize) -> Frames<'_> {
+        Frames {
+            socket: self,
+            max_len,
+        }
+    }
+
+    /// Returns the wrapped socket.
+    #[inline]
+    pub fn get_ref(&self) -> &Socket {
+        self.inner.get_ref()
+    }
+
+    /// Deregisters the socket from the reactor and returns it. It stays in non-blocking mode.
+    #[inline]
+    pub fn into_inner(self) -> Socket {
+        self.inner.into_inner()
+    }
+}
+
+impl AsRawFd for AsyncSocket {
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl AsFd for AsyncSocket {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.get_ref().as_fd()
+    }
+}
+
+/// The [`Stream`] returned by [`AsyncSocket::frames`].
+pub struct Frames<'a> {
+    socket: &'a AsyncSocket,
+    max_len: usize,
+}
+
+impl Stream for Frames<'_> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let mut guard = ready!(self.socket.inner.poll_read_ready(cx))?;
+            let mut frame = vec![0; self.max_len];
+            match guard.try_io(|inner| recv_nonblocking(inner.get_ref(), &mut frame)) {
+                Ok(Ok(len)) => {
+                    frame.truncate(len);
+                    return Poll::Ready(Some(Ok(frame)));
+                }
+                Ok(Err(err)) => return Poll::Ready(Some(Err(err))),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+// Without a timeout, libraw reads right away; on a non-blocking socket that fails with EAGAIN,
+// which `io::Error` reports as `WouldBlock` and `try_io` turns into clearing the readiness.
+fn recv_nonblocking(socket: &Socket, destination: &mut [u8]) -> io::Result<usize> {
+    match socket.read_shared(destination, None)? {
+        ReadOutcome::Frame(len) => Ok(len),
+        ReadOutcome::TimedOut => unreachable!("reads without a timeout cannot time out"),
+    }
+}
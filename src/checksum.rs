@@ -0,0 +1,809 @@
+// Checksums: Rust bindings of libraw's checksum.h and native implementations
+//
+// Copyright © 2023 Josef Schönberger
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included
+// in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES
+// OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.
+// IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT,
+// TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
+// OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+#[cfg(not(feature = "pure-rust"))]
+use std::ffi::c_void;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+// ---------------------------- checksum.h ------------------------------
+
+// With the "pure-rust" feature, `icmp6_chksum` and `crc32` are implemented natively below.
+#[cfg(not(feature = "pure-rust"))]
+extern "C" {
+    fn icmp6_checksum(hdr: *const [u8; 40], payload: *const u8, len: usize) -> u16;
+    fn get_crc32(frame: *const c_void, length: usize) -> u32;
+}
+
+#[cfg(not(feature = "pure-rust"))]
+#[inline]
+pub fn icmp6_chksum(hdr: &[u8; 40], payload: &[u8]) -> u16 {
+    unsafe { icmp6_checksum(hdr as _, payload.as_ptr(), payload.len()) }
+}
+
+#[cfg(not(feature = "pure-rust"))]
+#[inline]
+pub fn crc32(data: &[u8]) -> u32 {
+    unsafe { get_crc32(data.as_ptr() as _, data.len()) }
+}
+
+// ----------------------- checksum (pure Rust) ------------------------
+
+// IP protocol numbers, which double as IPv6 next header values.
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+const IPPROTO_ICMPV6: u8 = 58;
+
+// Adds up `data` as big-endian 16-bit words (RFC 1071). An odd trailing byte is padded with zero.
+fn ones_complement_sum(mut sum: u64, data: &[u8]) -> u64 {
+    let mut words = data.chunks_exact(2);
+    for word in &mut words {
+        sum += u16::from_be_bytes([word[0], word[1]]) as u64;
+    }
+    if let [last] = words.remainder() {
+        sum += (*last as u64) << 8;
+    }
+    sum
+}
+
+// Folds the carries of `sum` back into the lower 16 bits.
+fn fold(mut sum: u64) -> u16 {
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum as u16
+}
+
+fn pseudo_header_sum_v4(src: Ipv4Addr, dst: Ipv4Addr, protocol: u8, len: usize) -> u64 {
+    let sum = ones_complement_sum(0, &src.octets());
+    let sum = ones_complement_sum(sum, &dst.octets());
+    sum + protocol as u64 + len as u64
+}
+
+fn pseudo_header_sum_v6(src: Ipv6Addr, dst: Ipv6Addr, next_header: u8, len: usize) -> u64 {
+    let sum = ones_complement_sum(0, &src.octets());
+    let sum = ones_complement_sum(sum, &dst.octets());
+    sum + next_header as u64 + len as u64
+}
+
+/// Computes the Internet checksum (RFC 1071) of `data`, e.g. an IPv4 header.
+///
+/// The checksum field inside `data` must be zero. Write the result in network byte order
+/// (`to_be_bytes()`).
+#[inline]
+pub fn internet_checksum(data: &[u8]) -> u16 {
+    !fold(ones_complement_sum(0, data))
+}
+
+/// Checks the Internet checksum of `data`, including its checksum field.
+#[inline]
+pub fn verify_internet_checksum(data: &[u8]) -> bool {
+    fold(ones_complement_sum(0, data)) == 0xffff
+}
+
+/// Computes the checksum of an ICMPv4 message whose checksum field is zero.
+#[inline]
+pub fn icmpv4_checksum(message: &[u8]) -> u16 {
+    internet_checksum(message)
+}
+
+/// Checks the checksum of an ICMPv4 message.
+#[inline]
+pub fn verify_icmpv4_checksum(message: &[u8]) -> bool {
+    verify_internet_checksum(message)
+}
+
+/// Computes the checksum of an ICMPv6 message whose checksum field is zero, including the IPv6
+/// pseudo-header.
+#[inline]
+pub fn icmpv6_checksum(src: Ipv6Addr, dst: Ipv6Addr, message: &[u8]) -> u16 {
+    let sum = pseudo_header_sum_v6(src, dst, IPPROTO_ICMPV6, message.len());
+    !fold(ones_complement_sum(sum, message))
+}
+
+/// Checks the checksum of an ICMPv6 message.
+#[inline]
+pub fn verify_icmpv6_checksum(src: Ipv6Addr, dst: Ipv6Addr, message: &[u8]) -> bool {
+    let sum = pseudo_header_sum_v6(src, dst, IPPROTO_ICMPV6, message.len());
+    fold(ones_complement_sum(sum, message)) == 0xffff
+}
+
+// A computed UDP checksum of zero is transmitted as all ones, as zero means "no checksum".
+fn udp_checksum(sum: u64, datagram: &[u8]) -> u16 {
+    match !fold(ones_complement_sum(sum, datagram)) {
+        0 => 0xffff,
+        checksum => checksum,
+    }
+}
+
+fn udp_checksum_field(datagram: &[u8]) -> Option<u16> {
+    datagram
+        .get(6..8)
+        .map(|field| u16::from_be_bytes([field[0], field[1]]))
+}
+
+/// Computes the checksum of a UDP datagram sent over IPv4, including the pseudo-header. The
+/// checksum field of `datagram` must be zero.
+#[inline]
+pub fn udp_ipv4_checksum(src: Ipv4Addr, dst: Ipv4Addr, datagram: &[u8]) -> u16 {
+    udp_checksum(
+        pseudo_header_sum_v4(src, dst, IPPROTO_UDP, datagram.len()),
+        datagram,
+    )
+}
+
+/// Checks the checksum of a UDP datagram received over IPv4. Datagrams without a checksum (a
+/// checksum field of zero) are accepted.
+#[inline]
+pub fn verify_udp_ipv4_checksum(src: Ipv4Addr, dst: Ipv4Addr, datagram: &[u8]) -> bool {
+    match udp_checksum_field(datagram) {
+        None => false,
+        Some(0) => true,
+        Some(_) => {
+            let sum = pseudo_header_sum_v4(src, dst, IPPROTO_UDP, datagram.len());
+            fold(ones_complement_sum(sum, datagram)) == 0xffff
+        }
+    }
+}
+
+/// Computes the checksum of a UDP datagram sent over IPv6, including the pseudo-header. The
+/// checksum field of `datagram` must be zero.
+#[inline]
+pub fn udp_ipv6_checksum(src: Ipv6Addr, dst: Ipv6Addr, datagram: &[u8]) -> u16 {
+    udp_checksum(
+        pseudo_header_sum_v6(src, dst, IPPROTO_UDP, datagram.len()),
+        datagram,
+    )
+}
+
+/// Checks the checksum of a UDP datagram received over IPv6. Unlike IPv4, IPv6 does not allow
+/// omitting the checksum, so a checksum field of zero is rejected.
+#[inline]
+pub fn verify_udp_ipv6_checksum(src: Ipv6Addr, dst: Ipv6Addr, datagram: &[u8]) -> bool {
+    match udp_checksum_field(datagram) {
+        None | Some(0) => false,
+        Some(_) => {
+            let sum = pseudo_header_sum_v6(src, dst, IPPROTO_UDP, datagram.len());
+            fold(ones_complement_sum(sum, datagram)) == 0xffff
+        }
+    }
+}
+
+/// Computes the checksum of a TCP segment sent over IPv4, including the pseudo-header. The
+/// checksum field of `segment` must be zero.
+#[inline]
+pub fn tcp_ipv4_checksum(src: Ipv4Addr, dst: Ipv4Addr, segment: &[u8]) -> u16 {
+    let sum = pseudo_header_sum_v4(src, dst, IPPROTO_TCP, segment.len());
+    !fold(ones_complement_sum(sum, segment))
+}
+
+/// Checks the checksum of a TCP segment received over IPv4.
+#[inline]
+pub fn verify_tcp_ipv4_checksum(src: Ipv4Addr, dst: Ipv4Addr, segment: &[u8]) -> bool {
+    let sum = pseudo_header_sum_v4(src, dst, IPPROTO_TCP, segment.len());
+    fold(ones_complement_sum(sum, segment)) == 0xffff
+}
+
+/// Computes the checksum of a TCP segment sent over IPv6, including the pseudo-header. The
+/// checksum field of `segment` must be zero.
+#[inline]
+pub fn tcp_ipv6_checksum(src: Ipv6Addr, dst: Ipv6Addr, segment: &[u8]) -> u16 {
+    let sum = pseudo_header_sum_v6(src, dst, IPPROTO_TCP, segment.len());
+    !fold(ones_complement_sum(sum, segment))
+}
+
+/// Checks the checksum of a TCP segment received over IPv6.
+#[inline]
+pub fn verify_tcp_ipv6_checksum(src: Ipv6Addr, dst: Ipv6Addr, segment: &[u8]) -> bool {
+    let sum = pseudo_header_sum_v6(src, dst, IPPROTO_TCP, segment.len());
+    fold(ones_complement_sum(sum, segment)) == 0xffff
+}
+
+/// Adjusts `checksum` after a 16-bit word of the checksummed data changed from `old` to `new`,
+/// without looking at the rest of the data (RFC 1624, eqn. 3).
+///
+/// Don't update the checksum of a UDP datagram whose checksum field is zero; it has none.
+#[inline]
+pub fn update_checksum(checksum: u16, old: u16, new: u16) -> u16 {
+    !fold(!checksum as u64 + !old as u64 + new as u64)
+}
+
+/// Adjusts `checksum` after the bytes at `offset` of the checksummed data changed from `old` to
+/// `new`, e.g. a hop limit or an address that was rewritten. For TCP and UDP, `offset` is relative
+/// to the start of the TCP or UDP header.
+///
+/// Panics if `old` and `new` differ in length.
+pub fn update_checksum_bytes(checksum: u16, offset: usize, old: &[u8], new: &[u8]) -> u16 {
+    assert_eq!(
+        old.len(),
+        new.len(),
+        "old and new bytes must have the same length"
+    );
+    // A byte at an odd offset is the lower half of its 16-bit word.
+    let sum_at = |data: &[u8]| match (offset % 2, data) {
+        (1, [first, rest @ ..]) => fold(ones_complement_sum(*first as u64, rest)),
+        _ => fold(ones_complement_sum(0, data)),
+    };
+    update_checksum(checksum, sum_at(old), sum_at(new))
+}
+
+// Without libraw, `icmp6_chksum` takes the addresses from the IPv6 header and defers to the
+// native implementation.
+#[cfg(feature = "pure-rust")]
+#[inline]
+pub fn icmp6_chksum(hdr: &[u8; 40], payload: &[u8]) -> u16 {
+    let src: [u8; 16] = hdr[8..24].try_into().unwrap();
+    let dst: [u8; 16] = hdr[24..40].try_into().unwrap();
+    icmpv6_checksum(src.into(), dst.into(), payload)
+}
+
+// ------------------------- crc32 (pure Rust) --------------------------
+
+// The CRC-32 used by IEEE 802.3, in its bit-reflected form.
+const CRC32_POLYNOMIAL: u32 = 0xedb8_8320;
+
+// `CRC32_TABLES[0]` is the classic byte-wise table. Table `n` advances a byte by `n` further zero
+// bytes, which lets slice-by-8 process eight bytes per iteration.
+static CRC32_TABLES: [[u32; 256]; 8] = crc32_tables();
+
+const fn crc32_tables() -> [[u32; 256]; 8] {
+    let mut tables = [[0; 256]; 8];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ CRC32_POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        tables[0][i] = crc;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < 256 {
+        let mut n = 1;
+        while n < 8 {
+            let previous = tables[n - 1][i];
+            tables[n][i] = (previous >> 8) ^ tables[0][(previous & 0xff) as usize];
+            n += 1;
+        }
+        i += 1;
+    }
+    tables
+}
+
+fn crc32_update_bytewise(crc: u32, data: &[u8]) -> u32 {
+    data.iter().fold(crc, |crc, &byte| {
+        (crc >> 8) ^ CRC32_TABLES[0][((crc ^ byte as u32) & 0xff) as usize]
+    })
+}
+
+fn crc32_update_slice_by_8(mut crc: u32, data: &[u8]) -> u32 {
+    let t = &CRC32_TABLES;
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        let lo = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) ^ crc;
+        let hi = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+        crc = t[7][(lo & 0xff) as usize]
+            ^ t[6][((lo >> 8) & 0xff) as usize]
+            ^ t[5][((lo >> 16) & 0xff) as usize]
+            ^ t[4][(lo >> 24) as usize]
+            ^ t[3][(hi & 0xff) as usize]
+            ^ t[2][((hi >> 8) & 0xff) as usize]
+            ^ t[1][((hi >> 16) & 0xff) as usize]
+            ^ t[0][(hi >> 24) as usize];
+    }
+    crc32_update_bytewise(crc, chunks.remainder())
+}
+
+fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    #[cfg(target_arch = "x86_64")]
+    if data.len() >= 128
+        && is_x86_feature_detected!("pclmulqdq")
+        && is_x86_feature_detected!("sse4.1")
+    {
+        return unsafe { crc32_pclmul::update(crc, data) };
+    }
+    crc32_update_slice_by_8(crc, data)
+}
+
+// Folds 64 bytes at a time using carry-less multiplication, following Intel's "Fast CRC
+// Computation for Generic Polynomials Using PCLMULQDQ Instruction". SSE4.2's `crc32` instruction
+// is of no use here: it implements the Castagnoli polynomial, not the one of IEEE 802.3.
+#[cfg(target_arch = "x86_64")]
+mod crc32_pclmul {
+    use std::arch::x86_64::*;
+
+    // x^(4*128+32) mod P, x^(4*128-32) mod P, x^(128+32) mod P, x^(128-32) mod P, x^64 mod P,
+    // x^32 mod P, all bit-reflected and shifted left by one; then P itself and floor(x^64 / P).
+    const K1: i64 = 0x1_5444_2bd4;
+    const K2: i64 = 0x1_c6e4_1596;
+    const K3: i64 = 0x1_7519_97d0;
+    const K4: i64 = 0x0_ccaa_009e;
+    const K5: i64 = 0x1_63cd_6124;
+    const P_X: i64 = 0x1_db71_0641;
+    const U_PRIME: i64 = 0x1_f701_1641;
+
+    /// Callers must check that `pclmulqdq` and `sse4.1` are available and that `data` holds at
+    /// least 128 bytes.
+    #[target_feature(enable = "pclmulqdq", enable = "sse2", enable = "sse4.1")]
+    pub unsafe fn update(crc: u32, mut data: &[u8]) -> u32 {
+        debug_assert!(data.len() >= 128);
+        let mut x3 = load(&mut data);
+        let mut x2 = load(&mut data);
+        let mut x1 = load(&mut data);
+        let mut x0 = load(&mut data);
+        x3 = _mm_xor_si128(x3, _mm_cvtsi32_si128(crc as i32));
+
+        let k1k2 = _mm_set_epi64x(K2, K1);
+        while data.len() >= 64 {
+            x3 = reduce128(x3, load(&mut data), k1k2);
+            x2 = reduce128(x2, load(&mut data), k1k2);
+            x1 = reduce128(x1, load(&mut data), k1k2);
+            x0 = reduce128(x0, load(&mut data), k1k2);
+        }
+
+        let k3k4 = _mm_set_epi64x(K4, K3);
+        let mut x = reduce128(x3, x2, k3k4);
+        x = reduce128(x, x1, k3k4);
+        x = reduce128(x, x0, k3k4);
+        while data.len() >= 16 {
+            x = reduce128(x, load(&mut data), k3k4);
+        }
+
+        // Reduce 128 bits to 64 bits
+        let low32 = _mm_set_epi32(0, 0, 0, !0);
+        let x = _mm_xor_si128(_mm_clmulepi64_si128(x, k3k4, 0x10), _mm_srli_si128(x, 8));
+        let x = _mm_xor_si128(
+            _mm_clmulepi64_si128(_mm_and_si128(x, low32), _mm_set_epi64x(0, K5), 0x00),
+            _mm_srli_si128(x, 4),
+        );
+
+        // Barrett reduction to 32 bits
+        let pu = _mm_set_epi64x(U_PRIME, P_X);
+        let t1 = _mm_clmulepi64_si128(_mm_and_si128(x, low32), pu, 0x10);
+        let t2 = _mm_clmulepi64_si128(_mm_and_si128(t1, low32), pu, 0x00);
+        let crc = _mm_extract_epi32(_mm_xor_si128(x, t2), 1) as u32;
+
+        super::crc32_update_slice_by_8(crc, data)
+    }
+
+    #[inline(always)]
+    unsafe fn load(data: &mut &[u8]) -> __m128i {
+        let value = _mm_loadu_si128(data.as_ptr() as *const __m128i);
+        *data = &data[16..];
+        value
+    }
+
+    #[inline(always)]
+    unsafe fn reduce128(a: __m128i, b: __m128i, keys: __m128i) -> __m128i {
+        let t1 = _mm_clmulepi64_si128(a, keys, 0x00);
+        let t2 = _mm_clmulepi64_si128(a, keys, 0x11);
+        _mm_xor_si128(_mm_xor_si128(b, t1), t2)
+    }
+}
+
+/// A streaming CRC-32 (IEEE 802.3) hasher, producing the same values as [`crc32`].
+///
+/// Uses slice-by-8 tables, or carry-less multiplication on x86_64 CPUs that support `pclmulqdq`.
+#[derive(Debug, Clone)]
+pub struct Crc32 {
+    state: u32,
+}
+
+impl Crc32 {
+    #[inline]
+    pub fn new() -> Self {
+        Crc32 { state: !0 }
+    }
+
+    /// Computes the CRC-32 of `data` in one go.
+    #[inline]
+    pub fn checksum(data: &[u8]) -> u32 {
+        let mut crc = Crc32::new();
+        crc.update(data);
+        crc.finalize()
+    }
+
+    #[inline]
+    pub fn update(&mut self, data: &[u8]) {
+        self.state = crc32_update(self.state, data);
+    }
+
+    /// Returns the CRC-32 of all data passed to [`Crc32::update`] so far.
+    #[inline]
+    pub fn finalize(&self) -> u32 {
+        !self.state
+    }
+
+    #[inline]
+    pub fn reset(&mut self) {
+        self.state = !0;
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Ethernet frames are padded to this length before the Frame Check Sequence is appended.
+const ETHERNET_MIN_LEN: usize = 60;
+const FCS_LEN: usize = 4;
+
+/// Pads `frame` (starting with the Ethernet header) to the minimum length of 60 bytes and appends
+/// its Frame Check Sequence. The FCS is transmitted least significant byte first.
+pub fn append_fcs(frame: &mut Vec<u8>) {
+    if frame.len() < ETHERNET_MIN_LEN {
+        frame.resize(ETHERNET_MIN_LEN, 0);
+    }
+    let fcs = Crc32::checksum(frame);
+    frame.extend_from_slice(&fcs.to_le_bytes());
+}
+
+/// Checks the Frame Check Sequence in the last four bytes of `frame`.
+#[inline]
+pub fn verify_fcs(frame: &[u8]) -> bool {
+    strip_fcs(frame).is_some()
+}
+
+/// Returns `frame` without its Frame Check Sequence, or `None` if the FCS does not match. Any
+/// padding is left in place.
+pub fn strip_fcs(frame: &[u8]) -> Option<&[u8]> {
+    let data = frame.get(..frame.len().checked_sub(FCS_LEN)?)?;
+    let fcs = &frame[data.len()..];
+    (Crc32::checksum(data).to_le_bytes() == fcs).then_some(data)
+}
+
+#[cfg(feature = "pure-rust")]
+#[inline]
+pub fn crc32(data: &[u8]) -> u32 {
+    Crc32::checksum(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // xorshift64, so the "random" inputs are the same on every run.
+    fn random_bytes(state: &mut u64, len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                *state ^= *state << 13;
+                *state ^= *state >> 7;
+                *state ^= *state << 17;
+                *state as u8
+            })
+            .collect()
+    }
+
+    fn crc32_bitwise(data: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for &byte in data {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xedb8_8320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        !crc
+    }
+
+    fn hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    // Packets sent over a veth pair with checksum offloading disabled, so the kernel computed
+    // their checksums in software. They start with the IP header.
+    const ICMPV6_ECHO_REQUEST: &str = "6007793f000d3a4020010db800000000000000000000000120010db8000\
+        0000000000000000000028000123d1234000170696e6721";
+    const ICMPV6_NEIGHBOR_SOLICITATION: &str = "6000000000203aff20010db8000000000000000000000001ff\
+        0200000000000000000001ff00000287009f4a0000000020010db80000000000000000000000020101622ca7f0\
+        74c0";
+    const UDP_IPV6: &str = "60063f640014114020010db800000000000000000000000120010db800000000000000\
+        0000000002c9ef270f0014265668656c6c6f2067726e767321";
+    const TCP_IPV6_SYN: &str = "6009ea070028064020010db800000000000000000000000120010db80000000000\
+        000000000000029d1c0050cf3f9fb400000000a002fd208f630000020405a00402080a2b4d286a000000000103\
+        030a";
+    const UDP_IPV4: &str = "45000027f84040004011ee1ac6336401c63364029c40270f00135b3268656c6c6f2067\
+        726e7673";
+    const TCP_IPV4_SYN: &str = "4500003c014740004006e50ac6336401c6336402a7d600504ff86f7600000000a0\
+        02faf0c8770000020405b40402080a918e3706000000000103030a";
+    const TCP_IPV4_RST: &str = "45000028000040004006e665c6336402c63364010050a7d6000000004ff86f7750\
+        140000f3cf0000";
+
+    fn ipv6(packet: &str) -> (Ipv6Addr, Ipv6Addr, Vec<u8>) {
+        let packet = hex(packet);
+        let src: [u8; 16] = packet[8..24].try_into().unwrap();
+        let dst: [u8; 16] = packet[24..40].try_into().unwrap();
+        (src.into(), dst.into(), packet[40..].to_vec())
+    }
+
+    fn ipv4(packet: &str) -> (Ipv4Addr, Ipv4Addr, Vec<u8>) {
+        let packet = hex(packet);
+        let header_len = usize::from(packet[0] & 0x0f) * 4;
+        let src: [u8; 4] = packet[12..16].try_into().unwrap();
+        let dst: [u8; 4] = packet[16..20].try_into().unwrap();
+        (src.into(), dst.into(), packet[header_len..].to_vec())
+    }
+
+    // Returns the checksum at `offset` and `data` with that field zeroed.
+    fn take_checksum(data: &[u8], offset: usize) -> (u16, Vec<u8>) {
+        let mut zeroed = data.to_vec();
+        zeroed[offset..offset + 2].fill(0);
+        (u16::from_be_bytes([data[offset], data[offset + 1]]), zeroed)
+    }
+
+    #[test]
+    fn ipv4_header_checksum() {
+        let packet = hex(TCP_IPV4_SYN);
+        let (expected, header) = take_checksum(&packet[..20], 10);
+        assert_eq!(internet_checksum(&header), expected);
+        assert!(verify_internet_checksum(&packet[..20]));
+    }
+
+    #[test]
+    fn icmpv6_known_answers() {
+        for packet in [ICMPV6_ECHO_REQUEST, ICMPV6_NEIGHBOR_SOLICITATION] {
+            let (src, dst, mut message) = ipv6(packet);
+            let (expected, zeroed) = take_checksum(&message, 2);
+            assert_eq!(icmpv6_checksum(src, dst, &zeroed), expected);
+            let header: [u8; 40] = hex(packet)[..40].try_into().unwrap();
+            assert_eq!(icmp6_chksum(&header, &zeroed), expected);
+            assert!(verify_icmpv6_checksum(src, dst, &message));
+            message[4] ^= 0x80;
+            assert!(!verify_icmpv6_checksum(src, dst, &message));
+        }
+    }
+
+    #[test]
+    fn udp_known_answers() {
+        let (src, dst, datagram) = ipv4(UDP_IPV4);
+        let (expected, zeroed) = take_checksum(&datagram, 6);
+        assert_eq!(udp_ipv4_checksum(src, dst, &zeroed), expected);
+        assert!(verify_udp_ipv4_checksum(src, dst, &datagram));
+        assert!(!verify_udp_ipv4_checksum(
+            dst,
+            Ipv4Addr::LOCALHOST,
+            &datagram
+        ));
+
+        let (src, dst, datagram) = ipv6(UDP_IPV6);
+        let (expected, zeroed) = take_checksum(&datagram, 6);
+        assert_eq!(udp_ipv6_checksum(src, dst, &zeroed), expected);
+        assert!(verify_udp_ipv6_checksum(src, dst, &datagram));
+        assert!(!verify_udp_ipv6_checksum(dst, src, &datagram[..19]));
+    }
+
+    #[test]
+    fn tcp_known_answers() {
+        for packet in [TCP_IPV4_SYN, TCP_IPV4_RST] {
+            let (src, dst, segment) = ipv4(packet);
+            let (expected, zeroed) = take_checksum(&segment, 16);
+            assert_eq!(tcp_ipv4_checksum(src, dst, &zeroed), expected);
+            assert!(verify_tcp_ipv4_checksum(src, dst, &segment));
+        }
+
+        let (src, dst, mut segment) = ipv6(TCP_IPV6_SYN);
+        let (expected, zeroed) = take_checksum(&segment, 16);
+        assert_eq!(tcp_ipv6_checksum(src, dst, &zeroed), expected);
+        assert!(verify_tcp_ipv6_checksum(src, dst, &segment));
+        segment[39] ^= 0x01;
+        assert!(!verify_tcp_ipv6_checksum(src, dst, &segment));
+    }
+
+    #[test]
+    fn udp_zero_checksum_is_sent_as_ones() {
+        let src = Ipv4Addr::new(198, 51, 100, 1);
+        let dst = Ipv4Addr::new(198, 51, 100, 2);
+        let mut datagram = hex("9c40270f00100000");
+        datagram.extend_from_slice(b"grnvs!\0\0");
+        // Fill the last word with the checksum, so that the sum becomes 0xffff.
+        let checksum = udp_ipv4_checksum(src, dst, &datagram);
+        datagram[14..16].copy_from_slice(&checksum.to_be_bytes());
+        assert_eq!(udp_ipv4_checksum(src, dst, &datagram), 0xffff);
+        datagram[6..8].copy_from_slice(&[0xff, 0xff]);
+        assert!(verify_udp_ipv4_checksum(src, dst, &datagram));
+    }
+
+    #[test]
+    fn udp_zero_checksum_field() {
+        // Over IPv4, a zero checksum means that the sender did not compute one.
+        let (src, dst, mut datagram) = ipv4(UDP_IPV4);
+        datagram[6..8].fill(0);
+        datagram[10] ^= 0xff;
+        assert!(verify_udp_ipv4_checksum(src, dst, &datagram));
+        assert!(!verify_udp_ipv4_checksum(src, dst, &datagram[..7]));
+
+        // IPv6 requires it, so a zero checksum is always wrong.
+        let (src, dst, mut datagram) = ipv6(UDP_IPV6);
+        datagram[6..8].fill(0);
+        assert!(!verify_udp_ipv6_checksum(src, dst, &datagram));
+    }
+
+    #[cfg(not(feature = "pure-rust"))]
+    #[test]
+    fn icmpv6_checksum_matches_libraw() {
+        let mut state = 0xfeed_face_cafe_beef;
+        for len in (4..200).chain([1232, 1452]) {
+            let addresses = random_bytes(&mut state, 32);
+            let mut message = random_bytes(&mut state, len);
+            message[2..4].fill(0);
+            let mut header = [0; 40];
+            header[0] = 0x60;
+            header[4..6].copy_from_slice(&(len as u16).to_be_bytes());
+            header[6] = IPPROTO_ICMPV6;
+            header[7] = 255;
+            header[8..40].copy_from_slice(&addresses);
+            let src: [u8; 16] = addresses[..16].try_into().unwrap();
+            let dst: [u8; 16] = addresses[16..].try_into().unwrap();
+            assert_eq!(
+                icmpv6_checksum(src.into(), dst.into(), &message),
+                icmp6_chksum(&header, &message),
+                "len {len}"
+            );
+        }
+    }
+
+    #[test]
+    fn incremental_update_matches_recompute() {
+        let mut state = 0x1624;
+        for _ in 0..2000 {
+            let len = 2 + random_bytes(&mut state, 1)[0] as usize;
+            let mut data = random_bytes(&mut state, len);
+            let checksum = internet_checksum(&data);
+            let [offset, changed] = random_bytes(&mut state, 2)[..] else {
+                unreachable!()
+            };
+            let offset = usize::from(offset) % len;
+            let changed = 1 + usize::from(changed) % (len - offset);
+            let old = data[offset..offset + changed].to_vec();
+            let new = random_bytes(&mut state, changed);
+            data[offset..offset + changed].copy_from_slice(&new);
+            assert_eq!(
+                update_checksum_bytes(checksum, offset, &old, &new),
+                internet_checksum(&data),
+                "offset {offset}, {changed} bytes of {len}"
+            );
+        }
+    }
+
+    #[test]
+    fn incremental_update_of_hop_limit() {
+        let (src, dst, message) = ipv6(ICMPV6_ECHO_REQUEST);
+        let mut packet = hex(ICMPV6_ECHO_REQUEST);
+        let checksum = internet_checksum(&packet[..40]);
+        packet[7] -= 1;
+        assert_eq!(
+            update_checksum_bytes(checksum, 7, &[0x40], &[0x3f]),
+            internet_checksum(&packet[..40])
+        );
+        // Rewriting a word of the message is the same as recomputing over the pseudo-header.
+        let (checksum, mut zeroed) = take_checksum(&message, 2);
+        zeroed[4..6].copy_from_slice(&[0xab, 0xcd]);
+        assert_eq!(
+            update_checksum(checksum, 0x1234, 0xabcd),
+            icmpv6_checksum(src, dst, &zeroed)
+        );
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(Crc32::checksum(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(Crc32::checksum(b""), 0);
+    }
+
+    #[test]
+    fn crc32_implementations_agree() {
+        let mut state = 0x1234_5678_9abc_def0;
+        for len in (0..600).chain([1000, 1514, 4096, 9018]) {
+            let data = random_bytes(&mut state, len);
+            let expected = crc32_bitwise(&data);
+            assert_eq!(
+                !crc32_update_bytewise(!0, &data),
+                expected,
+                "bytewise, len {len}"
+            );
+            assert_eq!(
+                !crc32_update_slice_by_8(!0, &data),
+                expected,
+                "slice-by-8, len {len}"
+            );
+            assert_eq!(Crc32::checksum(&data), expected, "Crc32, len {len}");
+            assert_eq!(crc32(&data), expected, "crc32, len {len}");
+            #[cfg(target_arch = "x86_64")]
+            if len >= 128
+                && is_x86_feature_detected!("pclmulqdq")
+                && is_x86_feature_detected!("sse4.1")
+            {
+                let pclmul = unsafe { crc32_pclmul::update(!0, &data) };
+                assert_eq!(!pclmul, expected, "pclmul, len {len}");
+            }
+        }
+    }
+
+    #[test]
+    fn crc32_split_updates() {
+        let mut state = 0x0bad_cafe_f00d_d00d;
+        let data = random_bytes(&mut state, 3000);
+        let expected = crc32_bitwise(&data);
+        for pieces in [2, 3, 7, 64] {
+            let mut cuts: Vec<usize> = random_bytes(&mut state, pieces - 1)
+                .iter()
+                .zip(random_bytes(&mut state, pieces - 1))
+                .map(|(&hi, lo)| (usize::from(hi) << 8 | usize::from(lo)) % data.len())
+                .collect();
+            cuts.push(0);
+            cuts.push(data.len());
+            cuts.sort_unstable();
+            let mut crc = Crc32::new();
+            for window in cuts.windows(2) {
+                crc.update(&data[window[0]..window[1]]);
+            }
+            assert_eq!(crc.finalize(), expected, "cuts {cuts:?}");
+        }
+
+        let mut crc = Crc32::default();
+        crc.update(b"garbage");
+        crc.reset();
+        crc.update(b"1234");
+        crc.update(b"56789");
+        assert_eq!(crc.finalize(), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn fcs_pads_short_frames() {
+        let mut frame = vec![0xff; 14];
+        append_fcs(&mut frame);
+        assert_eq!(frame.len(), 64);
+        assert!(frame[14..60].iter().all(|&byte| byte == 0));
+        assert_eq!(frame[60..], crc32_bitwise(&frame[..60]).to_le_bytes());
+        assert!(verify_fcs(&frame));
+        assert_eq!(strip_fcs(&frame).map(<[u8]>::len), Some(60));
+    }
+
+    #[test]
+    fn fcs_of_long_frames() {
+        let mut state = 0x5eed;
+        let data = random_bytes(&mut state, 1514);
+        let mut frame = data.clone();
+        append_fcs(&mut frame);
+        assert_eq!(frame.len(), 1518);
+        assert_eq!(strip_fcs(&frame), Some(&data[..]));
+
+        frame[100] ^= 0x01;
+        assert!(!verify_fcs(&frame));
+        assert_eq!(strip_fcs(&frame), None);
+        assert_eq!(strip_fcs(&[0; 3]), None);
+    }
+}
@@ -0,0 +1,928 @@
+// Hexdumps: a native replacement of libraw's hexdump.h
+//
+// Copyright © 2023 Josef Schönberger
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included
+// in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES
+// OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.
+// IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT,
+// TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
+// OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::ops::Range;
+
+// ----------------------------- hexdump.h -------------------------------
+
+// libraw's `hexdump_str` is limited to 17760 bytes, so the layout is reproduced below. The
+// declaration remains to check that both agree.
+#[cfg(all(test, not(feature = "pure-rust")))]
+extern "C" {
+    fn hexdump_str(buffer: *const std::ffi::c_void, len: isize) -> *const std::ffi::c_char;
+}
+
+/// Formats the wrapped bytes like libraw's `hexdump_str`, one line per 16 bytes:
+///
+/// ```text
+/// 0000  33 33 00 00 00 16 52 54  00 12 34 56 86 dd 60 00  33....RT..4V..`.
+/// ```
+///
+/// There is no limit on the length of the data. Use [`HexdumpOptions`] for other layouts.
+#[derive(Debug, Clone, Copy)]
+pub struct Hexdump<'a>(pub &'a [u8]);
+
+impl Display for Hexdump<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        HexdumpOptions::new().fmt_data(f, self.0)
+    }
+}
+
+/// The base in which [`HexdumpOptions`] prints offsets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OffsetBase {
+    Hexadecimal,
+    Decimal,
+    Octal,
+}
+
+/// ANSI terminal colors for highlighting byte ranges in a hexdump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+}
+
+impl Color {
+    fn ansi_code(self) -> u8 {
+        match self {
+            Color::Red => 31,
+            Color::Green => 32,
+            Color::Yellow => 33,
+            Color::Blue => 34,
+            Color::Magenta => 35,
+            Color::Cyan => 36,
+        }
+    }
+}
+
+/// Configures the layout of a hexdump. The defaults reproduce libraw's layout, see [`Hexdump`].
+///
+/// ```text
+/// let options = HexdumpOptions::new()
+///     .group_size(4)
+///     .highlight(0..40, Color::Blue)
+///     .highlight(40..48, Color::Green);
+/// print!("{}", options.display(&packet));
+/// ```
+#[derive(Debug, Clone)]
+pub struct HexdumpOptions {
+    bytes_per_line: usize,
+    group_size: usize,
+    offset_width: usize,
+    offset_base: OffsetBase,
+    ascii: bool,
+    compact: bool,
+    highlights: Vec<(Range<usize>, Color)>,
+}
+
+impl HexdumpOptions {
+    pub fn new() -> Self {
+        HexdumpOptions {
+            bytes_per_line: 16,
+            group_size: 8,
+            offset_width: 4,
+            offset_base: OffsetBase::Hexadecimal,
+            ascii: true,
+            compact: false,
+            highlights: Vec::new(),
+        }
+    }
+
+    /// Panics if `bytes_per_line` is zero.
+    pub fn bytes_per_line(mut self, bytes_per_line: usize) -> Self {
+        assert!(
+            bytes_per_line > 0,
+            "a hexdump needs at least one byte per line"
+        );
+        self.bytes_per_line = bytes_per_line;
+        self
+    }
+
+    /// Inserts an extra space after every `group_size` bytes. Zero disables grouping.
+    pub fn group_size(mut self, group_size: usize) -> Self {
+        self.group_size = group_size;
+        self
+    }
+
+    /// The minimum number of digits of the offsets, padded with zeros.
+    pub fn offset_width(mut self, offset_width: usize) -> Self {
+        self.offset_width = offset_width;
+        self
+    }
+
+    pub fn offset_base(mut self, offset_base: OffsetBase) -> Self {
+        self.offset_base = offset_base;
+        self
+    }
+
+    /// Whether to print the printable ASCII characters next to the bytes.
+    pub fn ascii(mut self, ascii: bool) -> Self {
+        self.ascii = ascii;
+        self
+    }
+
+    /// Prints all bytes on a single line without offsets and ASCII column, e.g.
+    /// `3333000000165254 0012345686dd6000` for log messages. Groups are separated by a space.
+    pub fn compact(mut self, compact: bool) -> Self {
+        self.compact = compact;
+        self
+    }
+
+    /// Colors the bytes in `range` using ANSI escape codes. If ranges overlap, the one added first
+    /// wins.
+    pub fn highlight(mut self, range: Range<usize>, color: Color) -> Self {
+        self.highlights.push((range, color));
+        self
+    }
+
+    /// Returns a value that formats `data` with these options.
+    pub fn display<'a>(&'a self, data: &'a [u8]) -> impl Display + 'a {
+        struct WithOptions<'a>(&'a HexdumpOptions, &'a [u8]);
+        impl Display for WithOptions<'_> {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                self.0.fmt_data(f, self.1)
+            }
+        }
+        WithOptions(self, data)
+    }
+
+    pub fn format(&self, data: &[u8]) -> String {
+        self.display(data).to_string()
+    }
+
+    pub fn write<W: fmt::Write>(&self, out: &mut W, data: &[u8]) -> fmt::Result {
+        write!(out, "{}", self.display(data))
+    }
+
+    pub fn write_io<W: io::Write>(&self, out: &mut W, data: &[u8]) -> io::Result<()> {
+        write!(out, "{}", self.display(data))
+    }
+
+    fn color_of(&self, offset: usize) -> Option<Color> {
+        self.highlights
+            .iter()
+            .find(|(range, _)| range.contains(&offset))
+            .map(|(_, color)| *color)
+    }
+
+    // Writes `text` for the byte at `offset`, colored if the byte is highlighted.
+    fn write_byte(&self, f: &mut Formatter<'_>, offset: usize, text: impl Display) -> fmt::Result {
+        match self.color_of(offset) {
+            Some(color) => write!(f, "\x1b[{}m{}\x1b[0m", color.ansi_code(), text),
+            None => write!(f, "{}", text),
+        }
+    }
+
+    fn starts_group(&self, index: usize) -> bool {
+        self.group_size != 0 && index != 0 && index % self.group_size == 0
+    }
+
+    fn fmt_data(&self, f: &mut Formatter<'_>, data: &[u8]) -> fmt::Result {
+        if self.compact {
+            for (offset, byte) in data.iter().enumerate() {
+                if self.starts_group(offset) {
+                    f.write_str(" ")?;
+                }
+                self.write_byte(f, offset, format_args!("{:02x}", byte))?;
+            }
+            return Ok(());
+        }
+
+        for (line, chunk) in data.chunks(self.bytes_per_line).enumerate() {
+            let start = line * self.bytes_per_line;
+            let width = self.offset_width;
+            match self.offset_base {
+                OffsetBase::Hexadecimal => write!(f, "{:0width$x}  ", start)?,
+                OffsetBase::Decimal => write!(f, "{:0width$}  ", start)?,
+                OffsetBase::Octal => write!(f, "{:0width$o}  ", start)?,
+            }
+            // Without the ASCII column, stop after the last byte instead of padding the line.
+            let columns = if self.ascii {
+                self.bytes_per_line
+            } else {
+                chunk.len()
+            };
+            for i in 0..columns {
+                if i != 0 {
+                    f.write_str(if self.starts_group(i) { "  " } else { " " })?;
+                }
+                match chunk.get(i) {
+                    Some(byte) => self.write_byte(f, start + i, format_args!("{:02x}", byte))?,
+                    None => f.write_str("  ")?,
+                }
+            }
+            if self.ascii {
+                f.write_str("  ")?;
+                for (i, &byte) in chunk.iter().enumerate() {
+                    self.write_byte(f, start + i, printable(byte))?;
+                }
+            }
+            f.write_str("\n")?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for HexdumpOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The character shown for `byte` in the ASCII column.
+fn printable(byte: u8) -> char {
+    if byte.is_ascii_graphic() || byte == b' ' {
+        byte as char
+    } else {
+        '.'
+    }
+}
+
+/// Writes a hexdump of `data` into any `fmt::Write`, e.g. a `String`.
+#[inline]
+pub fn write_hexdump<W: fmt::Write>(out: &mut W, data: &[u8]) -> fmt::Result {
+    write!(out, "{}", Hexdump(data))
+}
+
+/// Writes a hexdump of `data` into any `io::Write`, e.g. a file or `stdout`.
+#[inline]
+pub fn write_hexdump_io<W: io::Write>(out: &mut W, data: &[u8]) -> io::Result<()> {
+    write!(out, "{}", Hexdump(data))
+}
+
+#[inline]
+pub fn print_hexdump_to_stderr(data: &[u8]) {
+    eprint!("{}", Hexdump(data));
+}
+
+#[inline]
+pub fn hexdump_to_string(data: &[u8]) -> String {
+    Hexdump(data).to_string()
+}
+
+/// An error while parsing a hexdump, see [`parse_hexdump`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseHexdumpError {
+    line: usize,
+}
+
+impl ParseHexdumpError {
+    /// The line (counting from 1) that could not be parsed.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl Display for ParseHexdumpError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {} of the hexdump contains no hex bytes", self.line)
+    }
+}
+
+impl std::error::Error for ParseHexdumpError {}
+
+/// Turns a hexdump back into bytes, e.g. to replay a frame or to use it as a test fixture.
+///
+/// Understands the output of [`hexdump_to_string`], Wireshark's "Copy as Hex Dump", `xxd`,
+/// `hexdump -C` and plain hex, each with or without offsets and ASCII column. A dump has offsets
+/// if every line starts with the number of bytes before it, and an ASCII column is recognized by
+/// matching the bytes before it. Lines of `*`, which `hexdump -C` prints instead of repeating a
+/// line, are expanded.
+pub fn parse_hexdump(dump: &str) -> Result<Vec<u8>, ParseHexdumpError> {
+    let lines: Vec<(usize, &str)> = dump
+        .lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .collect();
+    // Whether there are offsets is decided for the whole dump, so that data which merely looks
+    // like an offset, e.g. "0000 0024 0001", is never dropped.
+    match parse_with_offsets(&lines) {
+        Some(result) => result,
+        None => parse_without_offsets(&lines),
+    }
+}
+
+// Parses a dump whose lines start with offsets. Returns `None` if they don't match the bytes.
+fn parse_with_offsets(lines: &[(usize, &str)]) -> Option<Result<Vec<u8>, ParseHexdumpError>> {
+    let mut bytes = Vec::new();
+    let mut previous = 0..0; // the bytes of the last line, for "*"
+    let mut repeated = false;
+    for (i, &(index, line)) in lines.iter().enumerate() {
+        let error = ParseHexdumpError { line: index + 1 };
+        if line == "*" {
+            repeated = true;
+            continue;
+        }
+        let mut tokens = line.split_whitespace();
+        let first = tokens.next().unwrap_or_default();
+        let (digits, colon) = match first.strip_suffix(':') {
+            Some(offset) => (offset.trim_start_matches("0x"), true),
+            None => (first, false),
+        };
+        // Every tool pads offsets to at least four digits.
+        if !is_hex(digits) || !colon && digits.len() < 4 {
+            return None;
+        }
+        let offset = usize::from_str_radix(digits, 16).ok()?;
+        if repeated {
+            repeated = false;
+            let missing = offset.checked_sub(bytes.len())?;
+            if previous.is_empty() || missing % previous.len() != 0 {
+                return Some(Err(error));
+            }
+            for _ in 0..missing / previous.len() {
+                bytes.extend_from_within(previous.clone());
+            }
+        }
+        if offset != bytes.len() {
+            return None;
+        }
+        // A single line only has an offset if it stands out, like "0000  33 33 ...".
+        let stands_out = colon || tokens.next().is_some_and(|next| next.len() < first.len());
+        if lines.len() == 1 && !stands_out {
+            return None;
+        }
+
+        let start = bytes.len();
+        let body = line[first.len()..].trim_start();
+        // Only a last line may hold nothing but the offset: `hexdump -C` ends with the length.
+        if !parse_hexdump_line(body, &mut bytes) && (i == 0 || i != lines.len() - 1) {
+            return Some(Err(error));
+        }
+        previous = start..bytes.len();
+    }
+    Some(Ok(bytes))
+}
+
+fn parse_without_offsets(lines: &[(usize, &str)]) -> Result<Vec<u8>, ParseHexdumpError> {
+    let mut bytes = Vec::new();
+    for &(index, line) in lines {
+        if !parse_hexdump_line(line, &mut bytes) {
+            return Err(ParseHexdumpError { line: index + 1 });
+        }
+    }
+    Ok(bytes)
+}
+
+// Appends the bytes of a hexdump line (without offset) to `bytes`. Returns false if there were
+// none.
+fn parse_hexdump_line(body: &str, bytes: &mut Vec<u8>) -> bool {
+    let start = bytes.len();
+    // After each hex token: where it ends in `body`, and how many bytes we had by then.
+    let mut candidates = vec![(0, start)];
+    for token in body.split_whitespace() {
+        if token.len() % 2 != 0 || !is_hex(token) {
+            break;
+        }
+        for pair in token.as_bytes().chunks(2) {
+            bytes.push((hex_value(pair[0]) << 4) | hex_value(pair[1]));
+        }
+        let end = token.as_ptr() as usize - body.as_ptr() as usize + token.len();
+        candidates.push((end, bytes.len()));
+    }
+
+    // The ASCII column may itself look like hex (e.g. "beef"), so prefer the longest prefix that is
+    // followed by the ASCII rendering of exactly these bytes. Otherwise, take all hex tokens.
+    let accepted = candidates
+        .iter()
+        .rev()
+        .find(|&&(end, len)| {
+            let rest = body[end..].trim();
+            let rest = rest
+                .strip_prefix('|')
+                .and_then(|rest| rest.strip_suffix('|'))
+                .unwrap_or(rest);
+            !rest.is_empty()
+                && bytes[start..len]
+                    .iter()
+                    .map(|&byte| printable(byte))
+                    .collect::<String>()
+                    .trim()
+                    == rest
+        })
+        .map_or(bytes.len(), |&(_, len)| len);
+    bytes.truncate(accepted);
+    accepted > start
+}
+
+fn is_hex(token: &str) -> bool {
+    !token.is_empty() && token.bytes().all(|c| c.is_ascii_hexdigit())
+}
+
+fn hex_value(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        _ => digit - b'A' + 10,
+    }
+}
+
+/// Shows two buffers side by side and marks the bytes in which they differ, e.g. in the message
+/// of a failed assertion:
+///
+/// ```text
+/// 1 of 60 bytes differ
+///       expected                   |  actual
+/// 0000  33 33 00 00 00 16 52 54    |  33 33 00 00 00 17 52 54
+///                      ^^          |                    ^^       <- Ethernet destination
+/// ```
+///
+/// Bytes that exist in only one buffer are shown as `--` on the other side.
+#[derive(Debug, Clone)]
+pub struct HexdumpDiff<'a> {
+    expected: &'a [u8],
+    actual: &'a [u8],
+    bytes_per_line: usize,
+    fields: Vec<(Range<usize>, String)>,
+    color: bool,
+}
+
+impl<'a> HexdumpDiff<'a> {
+    pub fn new(expected: &'a [u8], actual: &'a [u8]) -> Self {
+        HexdumpDiff {
+            expected,
+            actual,
+            bytes_per_line: 8,
+            fields: Vec::new(),
+            color: false,
+        }
+    }
+
+    /// Panics if `bytes_per_line` is zero.
+    pub fn bytes_per_line(mut self, bytes_per_line: usize) -> Self {
+        assert!(
+            bytes_per_line > 0,
+            "a hexdump needs at least one byte per line"
+        );
+        self.bytes_per_line = bytes_per_line;
+        self
+    }
+
+    /// Names the protocol field at `range`. Lines with differing bytes inside it mention the name.
+    pub fn field(mut self, range: Range<usize>, name: impl Into<String>) -> Self {
+        self.fields.push((range, name.into()));
+        self
+    }
+
+    /// Additionally prints differing bytes in red using ANSI escape codes.
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    fn differs(&self, offset: usize) -> bool {
+        self.expected.get(offset) != self.actual.get(offset)
+    }
+
+    fn fmt_side(
+        &self,
+        f: &mut Formatter<'_>,
+        data: &[u8],
+        line: Range<usize>,
+        pad: bool,
+    ) -> fmt::Result {
+        for (i, offset) in line.clone().enumerate() {
+            if i != 0 {
+                f.write_str(" ")?;
+            }
+            let cell = match data.get(offset) {
+                Some(byte) => format!("{:02x}", byte),
+                None => "--".to_string(),
+            };
+            if self.color && self.differs(offset) {
+                write!(f, "\x1b[{}m{}\x1b[0m", Color::Red.ansi_code(), cell)?;
+            } else {
+                f.write_str(&cell)?;
+            }
+        }
+        // Pad short lines so that both sides stay aligned.
+        if pad {
+            for _ in line.len()..self.bytes_per_line {
+                f.write_str("   ")?;
+            }
+        }
+        Ok(())
+    }
+
+    // The line under `line` marking differing bytes, padded to the full width.
+    fn markers(&self, line: Range<usize>) -> String {
+        let mut markers = line
+            .map(|offset| if self.differs(offset) { "^^" } else { "  " })
+            .collect::<Vec<_>>()
+            .join(" ");
+        markers.push_str(&" ".repeat(self.bytes_per_line * 3 - 1 - markers.len()));
+        markers
+    }
+}
+
+impl Display for HexdumpDiff<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let len = self.expected.len().max(self.actual.len());
+        let differing = (0..len).filter(|&offset| self.differs(offset)).count();
+        write!(f, "{} of {} bytes differ", differing, len)?;
+        if self.expected.len() != self.actual.len() {
+            write!(
+                f,
+                " (expected {} bytes, got {})",
+                self.expected.len(),
+                self.actual.len()
+            )?;
+        }
+        let side_width = self.bytes_per_line * 3 - 1;
+        write!(f, "\n      {:side_width$}  |  actual\n", "expected")?;
+
+        for start in (0..len).step_by(self.bytes_per_line) {
+            let line = start..(start + self.bytes_per_line).min(len);
+            write!(f, "{:04x}  ", start)?;
+            self.fmt_side(f, self.expected, line.clone(), true)?;
+            f.write_str("  |  ")?;
+            self.fmt_side(f, self.actual, line.clone(), false)?;
+            f.write_str("\n")?;
+
+            if !line.clone().any(|offset| self.differs(offset)) {
+                continue;
+            }
+            let markers = self.markers(line.clone());
+            let names: Vec<&str> = self
+                .fields
+                .iter()
+                .filter(|(range, _)| {
+                    line.clone()
+                        .any(|offset| range.contains(&offset) && self.differs(offset))
+                })
+                .map(|(_, name)| name.as_str())
+                .collect();
+            // Don't end the line in spaces, unless the names follow.
+            write!(f, "      {}  |  ", markers)?;
+            if names.is_empty() {
+                writeln!(f, "{}", markers.trim_end())?;
+            } else {
+                writeln!(f, "{}  <- {}", markers, names.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Shorthand for `HexdumpDiff::new(expected, actual).to_string()`.
+#[inline]
+pub fn hexdump_diff(expected: &[u8], actual: &[u8]) -> String {
+    HexdumpDiff::new(expected, actual).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An IPv6 multicast frame, cut short after "hi beef" so the ASCII column looks like hex.
+    const FRAME: &[u8] = b"\x33\x33\x00\x00\x00\x16\x52\x54\x00\x12\x34\x56\x86\xdd\x60\x00\
+        \x00\x00\x00\x24\x00\x01hi beef";
+
+    #[test]
+    fn libraw_layout() {
+        assert_eq!(hexdump_to_string(&[]), "");
+        assert_eq!(
+            hexdump_to_string(FRAME),
+            "\
+0000  33 33 00 00 00 16 52 54  00 12 34 56 86 dd 60 00  33....RT..4V..`.
+0010  00 00 00 24 00 01 68 69  20 62 65 65 66           ...$..hi beef
+"
+        );
+    }
+
+    #[test]
+    fn longer_than_libraw_allows() {
+        let data: Vec<u8> = (0..=255).cycle().take(17770).collect();
+        let dump = hexdump_to_string(&data);
+        assert_eq!(dump.lines().count(), 1111);
+        assert!(dump.ends_with(
+            "\
+4550  50 51 52 53 54 55 56 57  58 59 5a 5b 5c 5d 5e 5f  PQRSTUVWXYZ[\\]^_
+4560  60 61 62 63 64 65 66 67  68 69                    `abcdefghi
+"
+        ));
+
+        // Offsets grow beyond four digits.
+        let data: Vec<u8> = (0..=255).cycle().take(0x10004).collect();
+        assert!(hexdump_to_string(&data).ends_with(
+            "\
+fff0  f0 f1 f2 f3 f4 f5 f6 f7  f8 f9 fa fb fc fd fe ff  ................
+10000  00 01 02 03                                       ....
+"
+        ));
+    }
+
+    // "ABCDEFGHIJKLMNOPQRST"
+    fn letters() -> Vec<u8> {
+        (b'A'..=b'T').collect()
+    }
+
+    #[test]
+    fn bytes_per_line() {
+        let options = HexdumpOptions::new().bytes_per_line(8);
+        assert_eq!(
+            options.format(&letters()),
+            "\
+0000  41 42 43 44 45 46 47 48  ABCDEFGH
+0008  49 4a 4b 4c 4d 4e 4f 50  IJKLMNOP
+0010  51 52 53 54              QRST
+"
+        );
+    }
+
+    #[test]
+    fn group_size() {
+        let options = HexdumpOptions::new().group_size(0);
+        assert_eq!(
+            options.format(&letters()),
+            "\
+0000  41 42 43 44 45 46 47 48 49 4a 4b 4c 4d 4e 4f 50  ABCDEFGHIJKLMNOP
+0010  51 52 53 54                                      QRST
+"
+        );
+        let options = HexdumpOptions::new().group_size(4);
+        assert_eq!(
+            options.format(&letters()),
+            "\
+0000  41 42 43 44  45 46 47 48  49 4a 4b 4c  4d 4e 4f 50  ABCDEFGHIJKLMNOP
+0010  51 52 53 54                                         QRST
+"
+        );
+    }
+
+    #[test]
+    fn offsets() {
+        let options = HexdumpOptions::new().offset_width(8);
+        assert_eq!(
+            options.format(&letters()),
+            "\
+00000000  41 42 43 44 45 46 47 48  49 4a 4b 4c 4d 4e 4f 50  ABCDEFGHIJKLMNOP
+00000010  51 52 53 54                                       QRST
+"
+        );
+        let options = HexdumpOptions::new().bytes_per_line(10);
+        assert_eq!(
+            options
+                .clone()
+                .offset_base(OffsetBase::Decimal)
+                .format(&letters()),
+            "\
+0000  41 42 43 44 45 46 47 48  49 4a  ABCDEFGHIJ
+0010  4b 4c 4d 4e 4f 50 51 52  53 54  KLMNOPQRST
+"
+        );
+        assert_eq!(
+            options.offset_base(OffsetBase::Octal).format(&letters()),
+            "\
+0000  41 42 43 44 45 46 47 48  49 4a  ABCDEFGHIJ
+0012  4b 4c 4d 4e 4f 50 51 52  53 54  KLMNOPQRST
+"
+        );
+    }
+
+    #[test]
+    fn without_ascii() {
+        let options = HexdumpOptions::new().ascii(false);
+        assert_eq!(
+            options.format(&letters()),
+            "\
+0000  41 42 43 44 45 46 47 48  49 4a 4b 4c 4d 4e 4f 50
+0010  51 52 53 54
+"
+        );
+    }
+
+    #[test]
+    fn compact() {
+        let options = HexdumpOptions::new().compact(true);
+        assert_eq!(
+            options.format(&letters()),
+            "4142434445464748 494a4b4c4d4e4f50 51525354"
+        );
+        assert_eq!(
+            options.group_size(0).format(&letters()),
+            "4142434445464748494a4b4c4d4e4f5051525354"
+        );
+    }
+
+    #[test]
+    fn highlight() {
+        // The ranges overlap at byte 2, which keeps the color added first.
+        let options = HexdumpOptions::new()
+            .highlight(1..3, Color::Red)
+            .highlight(2..5, Color::Green);
+        let red = |text| format!("\x1b[31m{}\x1b[0m", text);
+        let green = |text| format!("\x1b[32m{}\x1b[0m", text);
+        let expected = format!(
+            "0000  41 {} {} {} {} 46{}  A{}{}{}{}F\n",
+            red("42"),
+            red("43"),
+            green("44"),
+            green("45"),
+            " ".repeat(31),
+            red("B"),
+            red("C"),
+            green("D"),
+            green("E"),
+        );
+        assert_eq!(options.format(&letters()[..6]), expected);
+    }
+
+    #[cfg(not(feature = "pure-rust"))]
+    #[test]
+    fn hexdump_matches_libraw() {
+        let data: Vec<u8> = (0..=255).cycle().take(17760).collect();
+        for len in (0..40).chain([255, 256, 1514, 17760]) {
+            let data = &data[..len];
+            let libraw = unsafe {
+                std::ffi::CStr::from_ptr(hexdump_str(data.as_ptr().cast(), len as isize))
+            };
+            assert_eq!(
+                hexdump_to_string(data),
+                libraw.to_str().unwrap(),
+                "len {len}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_libraw() {
+        assert_eq!(parse_hexdump(&hexdump_to_string(FRAME)).unwrap(), FRAME);
+        let options = HexdumpOptions::new().ascii(false);
+        assert_eq!(parse_hexdump(&options.format(FRAME)).unwrap(), FRAME);
+        let options = HexdumpOptions::new().compact(true);
+        assert_eq!(parse_hexdump(&options.format(FRAME)).unwrap(), FRAME);
+    }
+
+    #[test]
+    fn parse_wireshark() {
+        let dump = "\
+0000   33 33 00 00 00 16 52 54 00 12 34 56 86 dd 60 00   33....RT..4V..`.
+0010   00 00 00 24 00 01 68 69 20 62 65 65 66            ...$..hi beef
+";
+        assert_eq!(parse_hexdump(dump).unwrap(), FRAME);
+    }
+
+    #[test]
+    fn parse_xxd() {
+        let dump = "\
+00000000: 3333 0000 0016 5254 0012 3456 86dd 6000  33....RT..4V..`.
+00000010: 0000 0024 0001 6869 2062 6565 66         ...$..hi beef
+";
+        assert_eq!(parse_hexdump(dump).unwrap(), FRAME);
+    }
+
+    #[test]
+    fn parse_hexdump_canonical() {
+        let dump = "\
+00000000  33 33 00 00 00 16 52 54  00 12 34 56 86 dd 60 00  |33....RT..4V..`.|
+00000010  00 00 00 24 00 01 68 69  20 62 65 65 66           |...$..hi beef|
+0000001d
+";
+        assert_eq!(parse_hexdump(dump).unwrap(), FRAME);
+    }
+
+    #[test]
+    fn parse_plain_hex() {
+        assert_eq!(
+            parse_hexdump("deadbeef 00 11").unwrap(),
+            [0xde, 0xad, 0xbe, 0xef, 0x00, 0x11]
+        );
+        let dump = "333300000016525400123456\n86dd600000000024\n0001 6869 2062 6565 66\n";
+        assert_eq!(parse_hexdump(dump).unwrap(), FRAME);
+    }
+
+    #[test]
+    fn parse_hex_that_looks_like_offsets() {
+        assert_eq!(
+            parse_hexdump("0000 0024 0001").unwrap(),
+            [0, 0, 0, 0x24, 0, 1]
+        );
+        assert_eq!(
+            parse_hexdump("00010203\n0004 0506").unwrap(),
+            [0, 1, 2, 3, 0, 4, 5, 6]
+        );
+        assert_eq!(parse_hexdump("0000").unwrap(), [0, 0]);
+        assert_eq!(parse_hexdump("0000  33 33").unwrap(), [0x33, 0x33]);
+    }
+
+    #[test]
+    fn parse_repeated_lines() {
+        let dump = "\
+00000000  ff ff ff ff ff ff 02 00  00 00 00 01 08 06 00 01  |................|
+00000010  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|
+*
+00000030  00 00 00 00 00 00 00 00  00 00 00 00              |............|
+0000003c
+";
+        let mut frame = b"\xff\xff\xff\xff\xff\xff\x02\0\0\0\0\x01\x08\x06\0\x01".to_vec();
+        frame.resize(60, 0);
+        assert_eq!(parse_hexdump(dump).unwrap(), frame);
+
+        // Without offsets, there is nothing to repeat up to.
+        let err = parse_hexdump("00 11\n*\n22 33").unwrap_err();
+        assert_eq!(err.line(), 2);
+    }
+
+    #[test]
+    fn parse_error_names_the_line() {
+        let err = parse_hexdump("0000  33 33\n\nnot hex\n").unwrap_err();
+        assert_eq!(err.line(), 3);
+    }
+
+    fn counting(len: u8) -> Vec<u8> {
+        (0..len).collect()
+    }
+
+    #[test]
+    fn diff_of_equal_buffers() {
+        let data = counting(12);
+        assert_eq!(
+            hexdump_diff(&data, &data),
+            "\
+0 of 12 bytes differ
+      expected                 |  actual
+0000  00 01 02 03 04 05 06 07  |  00 01 02 03 04 05 06 07
+0008  08 09 0a 0b              |  08 09 0a 0b
+"
+        );
+    }
+
+    #[test]
+    fn diff_names_the_field() {
+        let expected = counting(12);
+        let mut actual = expected.clone();
+        actual[5] = 0x17;
+        let diff = HexdumpDiff::new(&expected, &actual)
+            .field(0..6, "Ethernet destination")
+            .field(6..12, "Ethernet source");
+        assert_eq!(
+            diff.to_string(),
+            "\
+1 of 12 bytes differ
+      expected                 |  actual
+0000  00 01 02 03 04 05 06 07  |  00 01 02 03 04 17 06 07
+                     ^^        |                 ^^        <- Ethernet destination
+0008  08 09 0a 0b              |  08 09 0a 0b
+"
+        );
+    }
+
+    #[test]
+    fn diff_of_unequal_lengths() {
+        let expected = counting(12);
+        assert_eq!(
+            hexdump_diff(&expected, &expected[..10]),
+            "\
+2 of 12 bytes differ (expected 12 bytes, got 10)
+      expected                 |  actual
+0000  00 01 02 03 04 05 06 07  |  00 01 02 03 04 05 06 07
+0008  08 09 0a 0b              |  08 09 -- --
+            ^^ ^^              |        ^^ ^^
+"
+        );
+    }
+
+    #[test]
+    fn diff_in_color() {
+        let diff = HexdumpDiff::new(&[0, 1, 2, 3], &[0, 1, 2])
+            .bytes_per_line(4)
+            .color(true);
+        assert_eq!(
+            diff.to_string(),
+            "\
+1 of 4 bytes differ (expected 4 bytes, got 3)
+      expected     |  actual
+0000  00 01 02 \x1b[31m03\x1b[0m  |  00 01 02 \x1b[31m--\x1b[0m
+               ^^  |           ^^
+"
+        );
+    }
+}
@@ -0,0 +1,497 @@
+// Virtual LANs: a simulated Ethernet segment with a learning switch, for tests
+//
+// Copyright © 2023 Josef Schönberger
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included
+// in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES
+// OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.
+// IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT,
+// TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
+// OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::packet_io::{address_setters, Addresses, PacketIo};
+use crate::raw::{Error, MacAddr, ReadOutcome};
+
+/// How a [`VirtualLan`] mistreats the frames it forwards.
+///
+/// The probabilities apply to every copy of a frame that is forwarded to a port. The decisions
+/// are drawn from a pseudo-random generator, so a given seed always produces the same pattern.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Impairments {
+    loss: f64,
+    duplication: f64,
+    reordering: f64,
+    delay: Duration,
+    seed: u64,
+}
+
+impl Impairments {
+    /// No impairments: every frame is delivered once, in order and right away.
+    #[inline]
+    pub fn new() -> Self {
+        Impairments {
+            loss: 0.0,
+            duplication: 0.0,
+            reordering: 0.0,
+            delay: Duration::ZERO,
+            seed: 0x9e37_79b9_7f4a_7c15,
+        }
+    }
+
+    /// The probability that a frame is dropped.
+    ///
+    /// Panics if `probability` is not within `0..=1`, e.g. if it is NaN.
+    #[inline]
+    pub fn loss(mut self, probability: f64) -> Self {
+        self.loss = checked_probability(probability);
+        self
+    }
+
+    /// The probability that a frame is delivered twice.
+    ///
+    /// Panics if `probability` is not within `0..=1`, e.g. if it is NaN.
+    #[inline]
+    pub fn duplication(mut self, probability: f64) -> Self {
+        self.duplication = checked_probability(probability);
+        self
+    }
+
+    /// The probability that a frame overtakes the frame queued before it, if that one has not
+    /// been received yet. Combine it with a [`delay`](Impairments::delay) so that frames queue up.
+    ///
+    /// Panics if `probability` is not within `0..=1`, e.g. if it is NaN.
+    #[inline]
+    pub fn reordering(mut self, probability: f64) -> Self {
+        self.reordering = checked_probability(probability);
+        self
+    }
+
+    /// How long a frame takes until it can be received.
+    #[inline]
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Seeds the pseudo-random generator that decides which frames are impaired.
+    #[inline]
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+impl Default for Impairments {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn checked_probability(probability: f64) -> f64 {
+    assert!(
+        (0.0..=1.0).contains(&probability),
+        "probability {} is not within 0..=1",
+        probability
+    );
+    probability
+}
+
+// xorshift64*: plenty for deciding which frames to impair, and reproducible from its seed.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed.max(1)) // the all-zero state would only ever produce zeros
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        if probability <= 0.0 {
+            return false;
+        }
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let value = self.0.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        ((value >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+}
+
+/// A simulated Ethernet segment: hosts attach to it like to a switch and receive each other's
+/// frames through their [`LanEndpoint`].
+///
+/// The switch learns which port a MAC address is behind from the source addresses of the frames
+/// it forwards. Unicast frames to known addresses only go to their port; broadcast, multicast and
+/// unicast frames to unknown addresses are flooded to all other ports. Frames shorter than an
+/// Ethernet header are dropped.
+///
+/// Clones refer to the same segment.
+#[derive(Clone)]
+pub struct VirtualLan {
+    switch: Arc<Mutex<Switch>>,
+}
+
+struct Switch {
+    ports: Vec<Option<Arc<Port>>>,
+    addresses: HashMap<MacAddr, usize>,
+    impairments: Impairments,
+    rng: Rng,
+}
+
+#[derive(Default)]
+struct Port {
+    // Sorted by the time the frames can be received.
+    inbox: Mutex<VecDeque<(Instant, Vec<u8>)>>,
+    arrived: Condvar,
+}
+
+impl VirtualLan {
+    /// Creates a segment without impairments.
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_impairments(Impairments::new())
+    }
+
+    /// Creates a segment that impairs the frames it forwards.
+    pub fn with_impairments(impairments: Impairments) -> Self {
+        VirtualLan {
+            switch: Arc::new(Mutex::new(Switch {
+                ports: Vec::new(),
+                addresses: HashMap::new(),
+                impairments,
+                rng: Rng::new(impairments.seed),
+            })),
+        }
+    }
+
+    /// Replaces the impairments for all frames forwarded from now on. This also reseeds the
+    /// pseudo-random generator.
+    pub fn set_impairments(&self, impairments: Impairments) {
+        let mut switch = lock(&self.switch);
+        switch.impairments = impairments;
+        switch.rng = Rng::new(impairments.seed);
+    }
+
+    /// Attaches a new host to the segment.
+    ///
+    /// The `n`th endpoint starts out as simulated host `n`, counting from 1, with the same
+    /// addresses as the ends of a [`MemorySocket`](crate::MemorySocket) pair.
+    pub fn attach(&self) -> LanEndpoint {
+        let mut switch = lock(&self.switch);
+        let port = Arc::new(Port::default());
+        switch.ports.push(Some(Arc::clone(&port)));
+        let index = switch.ports.len() - 1;
+        let host = u16::try_from(index + 1).unwrap_or(u16::MAX);
+        LanEndpoint {
+            lan: self.clone(),
+            index,
+            port,
+            addresses: Addresses::of_host(host),
+        }
+    }
+}
+
+impl Default for VirtualLan {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Switch {
+    fn forward(&mut self, from: usize, frame: &[u8]) {
+        if frame.len() < 14 {
+            return;
+        }
+        let destination = MacAddr(frame[0..6].try_into().unwrap());
+        let source = MacAddr(frame[6..12].try_into().unwrap());
+        if source.is_unicast() {
+            self.addresses.insert(source, from);
+        }
+        match self.addresses.get(&destination) {
+            Some(&to) if destination.is_unicast() => {
+                if to != from {
+                    self.deliver(to, frame);
+                }
+            }
+            _ => {
+                for to in 0..self.ports.len() {
+                    if to != from {
+                        self.deliver(to, frame);
+                    }
+                }
+            }
+        }
+    }
+
+    fn deliver(&mut self, to: usize, frame: &[u8]) {
+        let Some(port) = self.ports[to].clone() else {
+            return;
+        };
+        let impairments = self.impairments;
+        if self.rng.chance(impairments.loss) {
+            return;
+        }
+        let copies = if self.rng.chance(impairments.duplication) {
+            2
+        } else {
+            1
+        };
+        let mut inbox = lock(&port.inbox);
+        for _ in 0..copies {
+            // Frames queued with a longer delay, before the impairments changed, may be due later.
+            let mut due = Instant::now() + impairments.delay;
+            let mut index = inbox.partition_point(|&(queued, _)| queued <= due);
+            if index > 0 && self.rng.chance(impairments.reordering) {
+                // Take the place of the frame before, which is due no later than this one.
+                index -= 1;
+                due = inbox[index].0;
+            }
+            inbox.insert(index, (due, frame.to_vec()));
+        }
+        port.arrived.notify_all();
+    }
+}
+
+/// A host's connection to a [`VirtualLan`], see [`VirtualLan::attach`].
+///
+/// It receives every frame the switch forwards to its port, like a packet socket on an interface
+/// in promiscuous mode. Dropping it detaches the host.
+pub struct LanEndpoint {
+    lan: VirtualLan,
+    index: usize,
+    port: Arc<Port>,
+    addresses: Addresses,
+}
+
+address_setters!(
+    LanEndpoint,
+    "The switch only learns addresses from the frames that are sent."
+);
+
+impl PacketIo for LanEndpoint {
+    fn recv(
+        &mut self,
+        destination: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<ReadOutcome, Error> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut inbox = lock(&self.port.inbox);
+        loop {
+            let now = Instant::now();
+            let due = inbox.front().map(|(due, _)| *due);
+            if due.is_some_and(|due| due <= now) {
+                let (_, frame) = inbox.pop_front().unwrap();
+                let len = frame.len().min(destination.len());
+                destination[..len].copy_from_slice(&frame[..len]);
+                return Ok(ReadOutcome::Frame(len));
+            }
+            if deadline.is_some_and(|deadline| deadline <= now) {
+                return Ok(ReadOutcome::TimedOut);
+            }
+            inbox = match due.into_iter().chain(deadline).min() {
+                Some(wake) => {
+                    let (inbox, _) = self
+                        .port
+                        .arrived
+                        .wait_timeout(inbox, wake - now)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner());
+                    inbox
+                }
+                None => self
+                    .port
+                    .arrived
+                    .wait(inbox)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner()),
+            };
+        }
+    }
+
+    fn send(&mut self, frame: &[u8]) -> Result<usize, Error> {
+        lock(&self.lan.switch).forward(self.index, frame);
+        Ok(frame.len())
+    }
+
+    #[inline]
+    fn get_hwaddr(&self) -> Result<MacAddr, Error> {
+        Ok(self.addresses.hwaddr)
+    }
+
+    #[inline]
+    fn get_ipaddr(&self) -> Ipv4Addr {
+        self.addresses.ipaddr
+    }
+
+    #[inline]
+    fn get_ip6addr(&self) -> Ipv6Addr {
+        self.addresses.ip6addr
+    }
+}
+
+impl Drop for LanEndpoint {
+    fn drop(&mut self) {
+        let mut switch = lock(&self.lan.switch);
+        switch.ports[self.index] = None;
+        let index = self.index;
+        switch.addresses.retain(|_, port| *port != index);
+    }
+}
+
+// A panic in another host's thread must not take the whole segment down with it.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet_io::recv_frame;
+
+    fn frame(destination: MacAddr, source: MacAddr, payload: &[u8]) -> Vec<u8> {
+        let mut frame = destination.octets().to_vec();
+        frame.extend(source.octets());
+        frame.extend([0x88, 0xb5]); // local experimental ethertype
+        frame.extend(payload);
+        frame
+    }
+
+    // Whatever has arrived by now.
+    fn recv(endpoint: &mut LanEndpoint) -> Option<Vec<u8>> {
+        recv_frame(endpoint, Some(Duration::ZERO))
+    }
+
+    #[test]
+    fn floods_broadcast_and_unknown_unicast() {
+        let lan = VirtualLan::new();
+        let (mut a, mut b, mut c) = (lan.attach(), lan.attach(), lan.attach());
+        let unknown = MacAddr::new(0x02, 0, 0, 0, 0, 0x99);
+        for destination in [MacAddr::BROADCAST, unknown] {
+            let sent = frame(destination, a.get_hwaddr().unwrap(), b"hello");
+            a.send(&sent).unwrap();
+            assert_eq!(recv(&mut b), Some(sent.clone()));
+            assert_eq!(recv(&mut c), Some(sent));
+            assert_eq!(recv(&mut a), None);
+        }
+    }
+
+    #[test]
+    fn learned_unicast_reaches_only_its_port() {
+        let lan = VirtualLan::new();
+        let (mut a, mut b, mut c) = (lan.attach(), lan.attach(), lan.attach());
+        let (a_mac, b_mac) = (a.get_hwaddr().unwrap(), b.get_hwaddr().unwrap());
+        b.send(&frame(MacAddr::BROADCAST, b_mac, b"announce"))
+            .unwrap();
+        recv(&mut a).unwrap();
+        recv(&mut c).unwrap();
+
+        let sent = frame(b_mac, a_mac, b"unicast");
+        a.send(&sent).unwrap();
+        assert_eq!(recv(&mut b), Some(sent));
+        assert_eq!(recv(&mut c), None);
+        // Frames to the sender's own port are not sent back to it.
+        a.send(&frame(a_mac, a_mac, b"loop")).unwrap();
+        assert_eq!(recv(&mut a), None);
+        assert_eq!(recv(&mut b), None);
+        // Runts are dropped.
+        a.send(&[0xff; 13]).unwrap();
+        assert_eq!(recv(&mut b), None);
+    }
+
+    #[test]
+    fn detached_port_is_forgotten() {
+        let lan = VirtualLan::new();
+        let (mut a, mut b, mut c) = (lan.attach(), lan.attach(), lan.attach());
+        let (a_mac, b_mac) = (a.get_hwaddr().unwrap(), b.get_hwaddr().unwrap());
+        b.send(&frame(MacAddr::BROADCAST, b_mac, b"announce"))
+            .unwrap();
+        recv(&mut a).unwrap();
+        recv(&mut c).unwrap();
+        drop(b);
+
+        // b's address is unknown again, so the frame is flooded to the remaining ports.
+        let sent = frame(b_mac, a_mac, b"anyone?");
+        a.send(&sent).unwrap();
+        assert_eq!(recv(&mut c), Some(sent));
+    }
+
+    // Sends 200 numbered frames from one host to another and returns the numbers received.
+    fn impaired_run(impairments: Impairments) -> Vec<u8> {
+        let lan = VirtualLan::with_impairments(impairments);
+        let (mut a, mut b) = (lan.attach(), lan.attach());
+        let a_mac = a.get_hwaddr().unwrap();
+        for number in 0..200 {
+            a.send(&frame(MacAddr::BROADCAST, a_mac, &[number]))
+                .unwrap();
+        }
+        std::iter::from_fn(|| recv(&mut b))
+            .map(|frame| frame[14])
+            .collect()
+    }
+
+    #[test]
+    fn impairments_are_reproducible() {
+        let impairments = Impairments::new()
+            .loss(0.1)
+            .duplication(0.1)
+            .reordering(0.1)
+            .seed(42);
+        let run = impaired_run(impairments);
+        assert_eq!(impaired_run(impairments), run);
+        assert_ne!(impaired_run(impairments.seed(43)), run);
+
+        let mut numbers = run.clone();
+        numbers.sort_unstable();
+        assert_ne!(numbers, run, "no frame was reordered");
+        numbers.dedup();
+        assert_ne!(numbers.len(), run.len(), "no frame was duplicated");
+        assert_ne!(numbers.len(), 200, "no frame was lost");
+
+        assert_eq!(
+            impaired_run(Impairments::new()),
+            (0..200).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "not within 0..=1")]
+    fn probability_out_of_range() {
+        Impairments::new().loss(1.5);
+    }
+
+    #[test]
+    #[should_panic(expected = "not within 0..=1")]
+    fn probability_nan() {
+        Impairments::new().reordering(f64::NAN);
+    }
+
+    #[test]
+    fn shorter_delay_is_not_held_up_by_earlier_frames() {
+        let lan = VirtualLan::with_impairments(Impairments::new().delay(Duration::from_secs(60)));
+        let (mut a, mut b) = (lan.attach(), lan.attach());
+        let a_mac = a.get_hwaddr().unwrap();
+        a.send(&frame(MacAddr::BROADCAST, a_mac, b"slow")).unwrap();
+        lan.set_impairments(Impairments::new());
+        let fast = frame(MacAddr::BROADCAST, a_mac, b"fast");
+        a.send(&fast).unwrap();
+        assert_eq!(recv(&mut b), Some(fast));
+        assert_eq!(recv(&mut b), None);
+    }
+}
@@ -0,0 +1,62 @@
+// Provides Rust bindings of GRnvS's libraw
+//
+// Copyright © 2023 Josef Schönberger
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included
+// in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES
+// OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.
+// IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT,
+// TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
+// OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Rust bindings of GRnvS's libraw, plus native replacements for its helpers.
+//!
+//! * [`raw`]: packet sockets ([`Socket`]), either through libraw or, with the `pure-rust` feature,
+//!   implemented in Rust.
+//! * [`packet_io`]: the [`PacketIo`] trait, which protocol code can use instead of [`Socket`].
+//! * [`memory`]: [`MemorySocket`], connected in-memory endpoints for testing [`PacketIo`] code.
+//! * [`lan`]: [`VirtualLan`], a simulated Ethernet segment with a learning switch and
+//!   configurable impairments.
+//! * [`pcap`]: [`PcapWriter`], which writes captures for Wireshark, e.g. as a [`Socket`]'s tap,
+//!   and [`PcapReader`], which reads pcap and pcapng files.
+//! * [`replay`]: [`Replay`], which feeds a capture to [`PacketIo`] code.
+//! * [`checksum`]: Internet checksums, CRC-32 and Ethernet FCS helpers.
+//! * [`hexdump`]: formatting, parsing and diffing hexdumps.
+//! * `async_socket`: with the `tokio` feature, `AsyncSocket` receives and sends frames without
+//!   blocking a thread per socket.
+//!
+//! Everything is re-exported at the crate root, so code written against the old `grnvs.rs` keeps
+//! working with `use grnvs::*;`.
+
+#[cfg(feature = "tokio")]
+pub mod async_socket;
+pub mod checksum;
+pub mod hexdump;
+pub mod lan;
+pub mod memory;
+pub mod packet_io;
+pub mod pcap;
+pub mod raw;
+pub mod replay;
+
+#[cfg(feature = "tokio")]
+pub use async_socket::*;
+pub use checksum::*;
+pub use hexdump::*;
+pub use lan::*;
+pub use memory::*;
+pub use packet_io::*;
+pub use pcap::*;
+pub use raw::*;
+pub use replay::*;
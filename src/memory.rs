@@ -0,0 +1,173 @@
+// In-memory sockets: a PacketIo backend for tests without a network interface
+//
+// Copyright © 2023 Josef Schönberger
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included
+// in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES
+// OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.
+// IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT,
+// TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
+// OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+use nix::errno::Errno;
+
+use crate::packet_io::{address_setters, Addresses, PacketIo};
+use crate::raw::{Error, MacAddr, Operation, ReadOutcome};
+
+/// One end of an in-memory connection: every frame sent on one end is received on the other.
+///
+/// The ends of a [`MemorySocket::pair`] start out as simulated hosts 1 and 2: host `n` has the
+/// addresses `02:00:00:00:00:n`, `10.0.0.n` and `fe80::n`.
+pub struct MemorySocket {
+    sender: Sender<Vec<u8>>,
+    receiver: Receiver<Vec<u8>>,
+    addresses: Addresses,
+}
+
+impl MemorySocket {
+    /// Returns two connected ends.
+    pub fn pair() -> (MemorySocket, MemorySocket) {
+        let (a_sender, b_receiver) = mpsc::channel();
+        let (b_sender, a_receiver) = mpsc::channel();
+        let end = |sender, receiver, host| MemorySocket {
+            sender,
+            receiver,
+            addresses: Addresses::of_host(host),
+        };
+        (end(a_sender, a_receiver, 1), end(b_sender, b_receiver, 2))
+    }
+}
+
+address_setters!(MemorySocket);
+
+// Like a pipe, an end whose peer was dropped reports ENOTCONN instead of blocking forever.
+fn disconnected(operation: Operation) -> Error {
+    Error::Os {
+        operation,
+        errno: Errno::ENOTCONN,
+        context: Some("the other end of the memory socket was dropped".to_string()),
+    }
+}
+
+impl PacketIo for MemorySocket {
+    /// Receives the next frame sent on the other end. Frames that were sent before the other end
+    /// was dropped are still delivered.
+    fn recv(
+        &mut self,
+        destination: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<ReadOutcome, Error> {
+        let frame = match timeout {
+            Some(timeout) => match self.receiver.recv_timeout(timeout) {
+                Ok(frame) => frame,
+                Err(RecvTimeoutError::Timeout) => return Ok(ReadOutcome::TimedOut),
+                Err(RecvTimeoutError::Disconnected) => return Err(disconnected(Operation::Read)),
+            },
+            None => self
+                .receiver
+                .recv()
+                .map_err(|_| disconnected(Operation::Read))?,
+        };
+        let len = frame.len().min(destination.len());
+        destination[..len].copy_from_slice(&frame[..len]);
+        Ok(ReadOutcome::Frame(len))
+    }
+
+    fn send(&mut self, frame: &[u8]) -> Result<usize, Error> {
+        self.sender
+            .send(frame.to_vec())
+            .map_err(|_| disconnected(Operation::Write))?;
+        Ok(frame.len())
+    }
+
+    #[inline]
+    fn get_hwaddr(&self) -> Result<MacAddr, Error> {
+        Ok(self.addresses.hwaddr)
+    }
+
+    #[inline]
+    fn get_ipaddr(&self) -> Ipv4Addr {
+        self.addresses.ipaddr
+    }
+
+    #[inline]
+    fn get_ip6addr(&self) -> Ipv6Addr {
+        self.addresses.ip6addr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_cross_the_pair() {
+        let (mut a, mut b) = MemorySocket::pair();
+        assert_eq!(a.send(b"ping").unwrap(), 4);
+        let mut buffer = [0; 16];
+        let outcome = b.recv(&mut buffer, None).unwrap();
+        assert_eq!(outcome, ReadOutcome::Frame(4));
+        assert_eq!(&buffer[..4], b"ping");
+
+        b.send(b"pong").unwrap();
+        let outcome = a.recv(&mut buffer, Some(Duration::from_secs(60))).unwrap();
+        assert_eq!(outcome, ReadOutcome::Frame(4));
+        assert_eq!(&buffer[..4], b"pong");
+
+        assert_eq!(a.get_ipaddr(), Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(b.get_hwaddr().unwrap(), MacAddr([0x02, 0, 0, 0, 0, 2]));
+    }
+
+    #[test]
+    fn long_frames_are_truncated() {
+        let (mut a, mut b) = MemorySocket::pair();
+        a.send(b"a long frame").unwrap();
+        let mut buffer = [0; 6];
+        let outcome = b.recv(&mut buffer, None).unwrap();
+        assert_eq!(outcome, ReadOutcome::Frame(6));
+        assert_eq!(&buffer, b"a long");
+    }
+
+    #[test]
+    fn empty_queue_times_out() {
+        let (_a, mut b) = MemorySocket::pair();
+        let outcome = b
+            .recv(&mut [0; 16], Some(Duration::from_millis(10)))
+            .unwrap();
+        assert_eq!(outcome, ReadOutcome::TimedOut);
+    }
+
+    #[test]
+    fn dropped_peer_is_not_connected() {
+        let (mut a, mut b) = MemorySocket::pair();
+        a.send(b"last words").unwrap();
+        drop(a);
+        // Frames sent before are still delivered.
+        let outcome = b.recv(&mut [0; 16], None).unwrap();
+        assert_eq!(outcome, ReadOutcome::Frame(10));
+
+        let err = b.recv(&mut [0; 16], None).unwrap_err();
+        assert_eq!(err.errno(), Some(Errno::ENOTCONN));
+        assert_eq!(err.operation(), Operation::Read);
+        let err = b.recv(&mut [0; 16], Some(Duration::ZERO)).unwrap_err();
+        assert_eq!(err.errno(), Some(Errno::ENOTCONN));
+        let err = b.send(b"anyone?").unwrap_err();
+        assert_eq!(err.errno(), Some(Errno::ENOTCONN));
+        assert_eq!(err.operation(), Operation::Write);
+    }
+}
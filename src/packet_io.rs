@@ -0,0 +1,240 @@
+// Packet IO: a trait abstracting over raw.rs's Socket and other backends
+//
+// Copyright © 2023 Josef Schönberger
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included
+// in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES
+// OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.
+// IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT,
+// TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
+// OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+use crate::raw::{Error, MacAddr, ReadOutcome, Socket};
+
+/// Sending and receiving frames on an interface.
+///
+/// Protocol code written against this trait instead of [`Socket`] can run against other backends,
+/// e.g. in `cargo test` without root or a real network interface.
+pub trait PacketIo {
+    /// Waits at most `timeout` for a frame and writes it into `destination`. Without a timeout,
+    /// waits until a frame arrives.
+    ///
+    /// Frames longer than `destination` are truncated.
+    fn recv(
+        &mut self,
+        destination: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<ReadOutcome, Error>;
+
+    /// Sends `frame` and returns the amount of bytes that were actually sent.
+    fn send(&mut self, frame: &[u8]) -> Result<usize, Error>;
+
+    /// The hardware address of the interface.
+    fn get_hwaddr(&self) -> Result<MacAddr, Error>;
+
+    /// The IPv4 address of the interface.
+    fn get_ipaddr(&self) -> Ipv4Addr;
+
+    /// The IPv6 address of the interface.
+    fn get_ip6addr(&self) -> Ipv6Addr;
+}
+
+impl PacketIo for Socket {
+    #[inline]
+    fn recv(
+        &mut self,
+        destination: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<ReadOutcome, Error> {
+        match timeout {
+            Some(timeout) => self.read_timeout(destination, timeout),
+            None => self.read(destination, None),
+        }
+    }
+
+    #[inline]
+    fn send(&mut self, frame: &[u8]) -> Result<usize, Error> {
+        self.write(frame)
+    }
+
+    #[inline]
+    fn get_hwaddr(&self) -> Result<MacAddr, Error> {
+        Socket::get_hwaddr(self)
+    }
+
+    #[inline]
+    fn get_ipaddr(&self) -> Ipv4Addr {
+        Socket::get_ipaddr(self)
+    }
+
+    #[inline]
+    fn get_ip6addr(&self) -> Ipv6Addr {
+        Socket::get_ip6addr(self)
+    }
+}
+
+macro_rules! forward_packet_io {
+    ($($ty:ty),*) => {$(
+        impl<T: PacketIo + ?Sized> PacketIo for $ty {
+            #[inline]
+            fn recv(
+                &mut self,
+                destination: &mut [u8],
+                timeout: Option<Duration>,
+            ) -> Result<ReadOutcome, Error> {
+                (**self).recv(destination, timeout)
+            }
+
+            #[inline]
+            fn send(&mut self, frame: &[u8]) -> Result<usize, Error> {
+                (**self).send(frame)
+            }
+
+            #[inline]
+            fn get_hwaddr(&self) -> Result<MacAddr, Error> {
+                (**self).get_hwaddr()
+            }
+
+            #[inline]
+            fn get_ipaddr(&self) -> Ipv4Addr {
+                (**self).get_ipaddr()
+            }
+
+            #[inline]
+            fn get_ip6addr(&self) -> Ipv6Addr {
+                (**self).get_ip6addr()
+            }
+        }
+    )*};
+}
+
+forward_packet_io!(&mut T, Box<T>);
+
+// The addresses a simulated backend reports, e.g. those of a `MemorySocket`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Addresses {
+    pub(crate) hwaddr: MacAddr,
+    pub(crate) ipaddr: Ipv4Addr,
+    pub(crate) ip6addr: Ipv6Addr,
+}
+
+impl Addresses {
+    // The addresses of the `n`th simulated host: `02:00:00:00:00:n`, `10.0.0.n` and `fe80::n`.
+    pub(crate) fn of_host(n: u16) -> Self {
+        let [hi, lo] = n.to_be_bytes();
+        Addresses {
+            hwaddr: MacAddr([0x02, 0, 0, 0, hi, lo]),
+            ipaddr: Ipv4Addr::new(10, 0, hi, lo),
+            ip6addr: Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, n),
+        }
+    }
+}
+
+// Implements the setters of the addresses a simulated backend keeps in its `addresses` field.
+// The optional string adds to the documentation of `set_hwaddr`.
+macro_rules! address_setters {
+    ($ty:ty $(, $hwaddr_doc:literal)?) => {
+        impl $ty {
+            /// Sets the address reported by [`PacketIo::get_hwaddr`](crate::PacketIo::get_hwaddr).
+            $(#[doc = concat!(" ", $hwaddr_doc)])?
+            #[inline]
+            pub fn set_hwaddr(&mut self, hwaddr: $crate::MacAddr) {
+                self.addresses.hwaddr = hwaddr;
+            }
+
+            /// Sets the address reported by [`PacketIo::get_ipaddr`](crate::PacketIo::get_ipaddr).
+            #[inline]
+            pub fn set_ipaddr(&mut self, ipaddr: std::net::Ipv4Addr) {
+                self.addresses.ipaddr = ipaddr;
+            }
+
+            /// Sets the address reported by
+            /// [`PacketIo::get_ip6addr`](crate::PacketIo::get_ip6addr).
+            #[inline]
+            pub fn set_ip6addr(&mut self, ip6addr: std::net::Ipv6Addr) {
+                self.addresses.ip6addr = ip6addr;
+            }
+        }
+    };
+}
+
+pub(crate) use address_setters;
+
+// Receives a frame in tests, or `None` if the timeout expires first.
+#[cfg(test)]
+pub(crate) fn recv_frame(io: &mut impl PacketIo, timeout: Option<Duration>) -> Option<Vec<u8>> {
+    let mut buffer = [0; 1514];
+    match io.recv(&mut buffer, timeout).unwrap() {
+        ReadOutcome::Frame(len) => Some(buffer[..len].to_vec()),
+        ReadOutcome::TimedOut => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemorySocket;
+
+    // Answers one frame by sending it back with the host's address in front, like a tiny
+    // responder written against the trait.
+    fn respond(mut io: impl PacketIo) -> Result<(), Error> {
+        let mut buffer = [0; 64];
+        let ReadOutcome::Frame(len) = io.recv(&mut buffer, Some(Duration::from_secs(60)))? else {
+            panic!("no frame to respond to");
+        };
+        let mut reply = io.get_ipaddr().octets().to_vec();
+        reply.extend(&buffer[..len]);
+        io.send(&reply)?;
+        Ok(())
+    }
+
+    fn reply(peer: &mut MemorySocket) -> Vec<u8> {
+        recv_frame(peer, Some(Duration::from_secs(60))).expect("no reply")
+    }
+
+    #[test]
+    fn forwarding_impls() {
+        let (mut host, mut peer) = MemorySocket::pair();
+        let expected = [&[10, 0, 0, 1][..], b"hi"].concat();
+
+        peer.send(b"hi").unwrap();
+        respond(&mut host).unwrap();
+        assert_eq!(reply(&mut peer), expected);
+
+        let mut boxed = Box::new(host);
+        peer.send(b"hi").unwrap();
+        respond(&mut boxed).unwrap();
+        assert_eq!(reply(&mut peer), expected);
+
+        let mut dynamic: Box<dyn PacketIo> = boxed;
+        assert_eq!(
+            dynamic.get_hwaddr().unwrap(),
+            MacAddr([0x02, 0, 0, 0, 0, 1])
+        );
+        assert_eq!(
+            dynamic.get_ip6addr(),
+            Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1)
+        );
+        peer.send(b"hi").unwrap();
+        respond(&mut dynamic).unwrap();
+        assert_eq!(reply(&mut peer), expected);
+
+        peer.send(b"hi").unwrap();
+        respond(dynamic).unwrap();
+        assert_eq!(reply(&mut peer), expected);
+    }
+}
@@ -0,0 +1,887 @@
+// Pcap files: writing and reading captures of Wireshark and tcpdump
+//
+// Copyright © 2023 Josef Schönberger
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included
+// in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES
+// OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.
+// IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT,
+// TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
+// OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::raw::Layer;
+
+// The magic numbers of classic pcap files with microsecond and nanosecond timestamps.
+const MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+const MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+const SNAPLEN: u32 = 262_144; // like tcpdump
+
+const PCAPNG_SECTION_HEADER: u32 = 0x0a0d_0d0a;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 1;
+const PCAPNG_SIMPLE_PACKET: u32 = 3;
+const PCAPNG_ENHANCED_PACKET: u32 = 6;
+
+const PCAPNG_EPB_FLAGS: u16 = 2;
+
+const ARPHRD_ETHER: u16 = 1;
+const PACKET_HOST: u16 = 0;
+const PACKET_OUTGOING: u16 = 4;
+
+/// The link-layer header type of the frames in a capture, as registered with tcpdump.org.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkType {
+    /// `LINKTYPE_ETHERNET`: Ethernet frames, as read from a [`Layer::SOCK_RAW`] socket.
+    Ethernet,
+    /// `LINKTYPE_LINUX_SLL`: Linux "cooked" captures, for packets without an Ethernet header.
+    LinuxSll,
+    /// `LINKTYPE_LINUX_SLL2`: the second version of Linux cooked captures.
+    LinuxSll2,
+    /// Any other link type, by its number. Frames are written and read as they are.
+    Other(u32),
+}
+
+impl LinkType {
+    /// The number of the link type, as stored in capture files.
+    #[inline]
+    pub fn code(self) -> u32 {
+        match self {
+            LinkType::Ethernet => 1,
+            LinkType::LinuxSll => 113,
+            LinkType::LinuxSll2 => 276,
+            LinkType::Other(code) => code,
+        }
+    }
+
+    /// The link type with the given number.
+    #[inline]
+    pub fn from_code(code: u32) -> Self {
+        match code {
+            1 => LinkType::Ethernet,
+            113 => LinkType::LinuxSll,
+            276 => LinkType::LinuxSll2,
+            code => LinkType::Other(code),
+        }
+    }
+
+    /// The length of the cooked header preceding the packets of the Linux cooked link types.
+    #[inline]
+    pub(crate) fn cooked_header_len(self) -> usize {
+        match self {
+            LinkType::LinuxSll => 16,
+            LinkType::LinuxSll2 => 20,
+            LinkType::Ethernet | LinkType::Other(_) => 0,
+        }
+    }
+
+    /// The link type matching the frames a socket opened with `layer` reads and writes.
+    #[inline]
+    pub fn for_layer(layer: Layer) -> Self {
+        match layer {
+            Layer::SOCK_RAW => LinkType::Ethernet,
+            Layer::SOCK_DGRAM => LinkType::LinuxSll,
+        }
+    }
+}
+
+/// Whether a frame was received or sent, which cooked captures record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Incoming,
+    Outgoing,
+}
+
+/// Writes frames to a classic libpcap file with nanosecond timestamps.
+///
+/// For [`LinkType::Ethernet`], frames are written as they are. For the cooked link types, frames
+/// are expected to start at the network layer, like those of a [`Layer::SOCK_DGRAM`] socket, and
+/// the cooked header is synthesized. Its protocol is derived from the IP version, since packet
+/// sockets don't report it, and is 0 for anything else, e.g. ARP; the link-layer addresses are
+/// left out.
+pub struct PcapWriter<W: Write> {
+    writer: W,
+    link_type: LinkType,
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// Writes the file header to `writer`.
+    pub fn new(mut writer: W, link_type: LinkType) -> io::Result<Self> {
+        let mut header = Vec::with_capacity(24);
+        header.extend_from_slice(&MAGIC_NANOS.to_le_bytes());
+        header.extend_from_slice(&2u16.to_le_bytes()); // version 2.4
+        header.extend_from_slice(&4u16.to_le_bytes());
+        header.extend_from_slice(&0i32.to_le_bytes()); // timestamps are in UTC
+        header.extend_from_slice(&0u32.to_le_bytes()); // accuracy, always 0
+        header.extend_from_slice(&SNAPLEN.to_le_bytes());
+        header.extend_from_slice(&link_type.code().to_le_bytes());
+        writer.write_all(&header)?;
+        Ok(PcapWriter { writer, link_type })
+    }
+
+    /// Records `frame` with the current time.
+    #[inline]
+    pub fn write_frame(&mut self, frame: &[u8], direction: Direction) -> io::Result<()> {
+        self.write_frame_at(SystemTime::now(), frame, direction)
+    }
+
+    /// Records `frame` with the given time.
+    pub fn write_frame_at(
+        &mut self,
+        timestamp: SystemTime,
+        frame: &[u8],
+        direction: Direction,
+    ) -> io::Result<()> {
+        let cooked = match self.link_type {
+            LinkType::Ethernet | LinkType::Other(_) => Vec::new(),
+            LinkType::LinuxSll => sll_header(frame, direction),
+            LinkType::LinuxSll2 => sll2_header(frame, direction),
+        };
+        // Frames never exceed the snap length, so they are always recorded in full.
+        let len = (cooked.len() + frame.len()) as u32;
+        let since_epoch = timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+
+        let mut record = Vec::with_capacity(16 + len as usize);
+        record.extend_from_slice(&(since_epoch.as_secs() as u32).to_le_bytes());
+        record.extend_from_slice(&since_epoch.subsec_nanos().to_le_bytes());
+        record.extend_from_slice(&len.to_le_bytes()); // captured length
+        record.extend_from_slice(&len.to_le_bytes()); // original length
+        record.extend_from_slice(&cooked);
+        record.extend_from_slice(frame);
+        self.writer.write_all(&record)
+    }
+
+    /// The link type given in the file header.
+    #[inline]
+    pub fn link_type(&self) -> LinkType {
+        self.link_type
+    }
+
+    #[inline]
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub(crate) fn boxed(self) -> PcapWriter<Box<dyn Write + Send>>
+    where
+        W: Send + 'static,
+    {
+        PcapWriter {
+            writer: Box::new(self.writer),
+            link_type: self.link_type,
+        }
+    }
+
+    /// Returns the underlying writer without flushing it.
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl PcapWriter<BufWriter<File>> {
+    /// Creates (or truncates) the file at `path` and writes the file header.
+    #[inline]
+    pub fn create(path: impl AsRef<Path>, link_type: LinkType) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), link_type)
+    }
+}
+
+/// A frame read from a capture by [`PcapReader`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub timestamp: SystemTime,
+    pub link_type: LinkType,
+    /// The captured bytes, starting with the link-layer header. They are truncated if the capture
+    /// was taken with a small snap length.
+    pub data: Vec<u8>,
+    /// Whether the frame was received or sent, if the capture tells. It is taken from the flags
+    /// of pcapng packets or else from the packet type of a Linux cooked header.
+    pub direction: Option<Direction>,
+}
+
+/// Reads the frames of a classic pcap or a pcapng file, e.g. one written by [`PcapWriter`],
+/// Wireshark or tcpdump.
+///
+/// Both byte orders and any timestamp resolution are supported. pcapng files may consist of
+/// several sections and interfaces; blocks that don't contain packets are skipped.
+pub struct PcapReader<R: Read> {
+    reader: R,
+    format: Format,
+}
+
+enum Format {
+    Pcap {
+        big_endian: bool,
+        nanos: bool,
+        link_type: LinkType,
+    },
+    Pcapng {
+        big_endian: bool,
+        interfaces: Vec<Interface>,
+    },
+}
+
+// What a pcapng interface description block tells about the packets captured on it.
+struct Interface {
+    link_type: LinkType,
+    units_per_second: u128,
+    offset: i64,
+}
+
+impl<R: Read> PcapReader<R> {
+    /// Reads the file header from `reader` and detects the format.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        let format = if u32::from_le_bytes(magic) == PCAPNG_SECTION_HEADER {
+            let mut length = [0; 4];
+            reader.read_exact(&mut length)?;
+            Format::Pcapng {
+                big_endian: read_section_header(&mut reader, length)?,
+                interfaces: Vec::new(),
+            }
+        } else {
+            let (big_endian, nanos) = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+                (MAGIC_MICROS, _) => (false, false),
+                (MAGIC_NANOS, _) => (false, true),
+                (_, MAGIC_MICROS) => (true, false),
+                (_, MAGIC_NANOS) => (true, true),
+                _ => return Err(invalid("not a pcap or pcapng file")),
+            };
+            let mut header = [0; 20];
+            reader.read_exact(&mut header)?;
+            // The upper bits of the link type may hold the length of the FCS.
+            let link_type = u32_at(&header, 16, big_endian) & 0x0fff_ffff;
+            Format::Pcap {
+                big_endian,
+                nanos,
+                link_type: LinkType::from_code(link_type),
+            }
+        };
+        Ok(PcapReader { reader, format })
+    }
+
+    /// Reads the next frame, or returns `None` at the end of the file.
+    pub fn next_record(&mut self) -> io::Result<Option<Record>> {
+        match &mut self.format {
+            Format::Pcap {
+                big_endian,
+                nanos,
+                link_type,
+            } => read_pcap_record(&mut self.reader, *big_endian, *nanos, *link_type),
+            Format::Pcapng {
+                big_endian,
+                interfaces,
+            } => read_pcapng_record(&mut self.reader, big_endian, interfaces),
+        }
+    }
+}
+
+impl PcapReader<BufReader<File>> {
+    /// Opens the file at `path` and reads its header.
+    #[inline]
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> Iterator for PcapReader<R> {
+    type Item = io::Result<Record>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+fn read_pcap_record(
+    reader: &mut impl Read,
+    big_endian: bool,
+    nanos: bool,
+    link_type: LinkType,
+) -> io::Result<Option<Record>> {
+    let mut header = [0; 16];
+    if !read_or_eof(reader, &mut header)? {
+        return Ok(None);
+    }
+    let fraction = u32_at(&header, 4, big_endian).into();
+    let since_epoch = Duration::from_secs(u32_at(&header, 0, big_endian).into())
+        + if nanos {
+            Duration::from_nanos(fraction)
+        } else {
+            Duration::from_micros(fraction)
+        };
+    let data = read_bytes(reader, u32_at(&header, 8, big_endian))?;
+    Ok(Some(Record {
+        timestamp: UNIX_EPOCH + since_epoch,
+        link_type,
+        direction: cooked_direction(link_type, &data),
+        data,
+    }))
+}
+
+fn read_pcapng_record(
+    reader: &mut impl Read,
+    big_endian: &mut bool,
+    interfaces: &mut Vec<Interface>,
+) -> io::Result<Option<Record>> {
+    loop {
+        let mut header = [0; 8];
+        if !read_or_eof(reader, &mut header)? {
+            return Ok(None);
+        }
+        let block_type = u32_at(&header, 0, *big_endian);
+        if block_type == PCAPNG_SECTION_HEADER {
+            // A new section may use another byte order and starts without interfaces.
+            *big_endian = read_section_header(reader, header[4..].try_into().unwrap())?;
+            interfaces.clear();
+            continue;
+        }
+        let length = u32_at(&header, 4, *big_endian);
+        if length < 12 || length % 4 != 0 {
+            return Err(invalid("malformed pcapng block"));
+        }
+        let block = read_bytes(reader, length - 8)?;
+        let body = &block[..block.len() - 4]; // without the trailing length
+        match block_type {
+            PCAPNG_INTERFACE_DESCRIPTION => interfaces.push(parse_interface(body, *big_endian)?),
+            PCAPNG_ENHANCED_PACKET => {
+                if body.len() < 20 {
+                    return Err(invalid("malformed pcapng enhanced packet block"));
+                }
+                let interface = interfaces
+                    .get(u32_at(body, 0, *big_endian) as usize)
+                    .ok_or_else(|| invalid("pcapng packet refers to an unknown interface"))?;
+                let units = u64::from(u32_at(body, 4, *big_endian)) << 32
+                    | u64::from(u32_at(body, 8, *big_endian));
+                let len = u32_at(body, 12, *big_endian) as usize;
+                let data = body[20..]
+                    .get(..len)
+                    .ok_or_else(|| invalid("malformed pcapng enhanced packet block"))?;
+                let mut direction = None;
+                let options = body.get(20 + len.next_multiple_of(4)..).unwrap_or_default();
+                parse_options(options, *big_endian, |code, value| {
+                    if code == PCAPNG_EPB_FLAGS && value.len() == 4 {
+                        // epb_flags: the lowest two bits tell the direction, 0 if unknown
+                        let flags = u32_at(value, 0, *big_endian);
+                        direction = match flags & 0b11 {
+                            1 => Some(Direction::Incoming),
+                            2 => Some(Direction::Outgoing),
+                            _ => None,
+                        };
+                    }
+                    Ok(())
+                })?;
+                return Ok(Some(Record {
+                    timestamp: interface.timestamp(units)?,
+                    link_type: interface.link_type,
+                    direction: direction.or_else(|| cooked_direction(interface.link_type, data)),
+                    data: data.to_vec(),
+                }));
+            }
+            PCAPNG_SIMPLE_PACKET => {
+                // Simple packet blocks belong to the first interface and carry no timestamp.
+                let interface = interfaces
+                    .first()
+                    .ok_or_else(|| invalid("pcapng packet refers to an unknown interface"))?;
+                if body.len() < 4 {
+                    return Err(invalid("malformed pcapng simple packet block"));
+                }
+                let len = (u32_at(body, 0, *big_endian) as usize).min(body.len() - 4);
+                let data = &body[4..4 + len];
+                return Ok(Some(Record {
+                    timestamp: UNIX_EPOCH,
+                    link_type: interface.link_type,
+                    direction: cooked_direction(interface.link_type, data),
+                    data: data.to_vec(),
+                }));
+            }
+            _ => {}
+        }
+    }
+}
+
+// Reads the rest of a section header block after its type and `length`, returning whether the
+// section is big endian.
+fn read_section_header(reader: &mut impl Read, length: [u8; 4]) -> io::Result<bool> {
+    let mut byte_order = [0; 4];
+    reader.read_exact(&mut byte_order)?;
+    let big_endian = match u32::from_le_bytes(byte_order) {
+        PCAPNG_BYTE_ORDER_MAGIC => false,
+        magic if magic.swap_bytes() == PCAPNG_BYTE_ORDER_MAGIC => true,
+        _ => return Err(invalid("malformed pcapng section header block")),
+    };
+    let length = u32_at(&length, 0, big_endian);
+    if length < 28 || length % 4 != 0 {
+        return Err(invalid("malformed pcapng section header block"));
+    }
+    read_bytes(reader, length - 12)?; // version, section length and options
+    Ok(big_endian)
+}
+
+fn parse_interface(body: &[u8], big_endian: bool) -> io::Result<Interface> {
+    if body.len() < 8 {
+        return Err(invalid("malformed pcapng interface description block"));
+    }
+    let mut interface = Interface {
+        link_type: LinkType::from_code(u16_at(body, 0, big_endian).into()),
+        units_per_second: 1_000_000,
+        offset: 0,
+    };
+    parse_options(&body[8..], big_endian, |code, value| {
+        match (code, value) {
+            (9, &[resolution]) => {
+                // if_tsresol: a negative power of 10, or of 2 if the top bit is set
+                let units = if resolution & 0x80 == 0 {
+                    10u128.checked_pow(resolution.into())
+                } else {
+                    2u128.checked_pow((resolution & 0x7f).into())
+                };
+                interface.units_per_second =
+                    units.ok_or_else(|| invalid("unsupported pcapng timestamp resolution"))?;
+            }
+            (14, _) if value.len() == 8 => {
+                // if_tsoffset: seconds to add to every timestamp
+                let offset = value.try_into().unwrap();
+                interface.offset = if big_endian {
+                    i64::from_be_bytes(offset)
+                } else {
+                    i64::from_le_bytes(offset)
+                };
+            }
+            _ => {}
+        }
+        Ok(())
+    })?;
+    Ok(interface)
+}
+
+// Calls `option` with the code and value of every option in a pcapng block's `options`.
+fn parse_options(
+    mut options: &[u8],
+    big_endian: bool,
+    mut option: impl FnMut(u16, &[u8]) -> io::Result<()>,
+) -> io::Result<()> {
+    while options.len() >= 4 {
+        let code = u16_at(options, 0, big_endian);
+        let len = usize::from(u16_at(options, 2, big_endian));
+        let value = options
+            .get(4..4 + len)
+            .ok_or_else(|| invalid("malformed pcapng option"))?;
+        if code == 0 {
+            break; // opt_endofopt
+        }
+        option(code, value)?;
+        options = options
+            .get(4 + len.next_multiple_of(4)..)
+            .unwrap_or_default();
+    }
+    Ok(())
+}
+
+impl Interface {
+    fn timestamp(&self, units: u64) -> io::Result<SystemTime> {
+        let units = u128::from(units);
+        let seconds = units / self.units_per_second;
+        let nanos = units % self.units_per_second * 1_000_000_000 / self.units_per_second;
+        let since_epoch = Duration::new(seconds as u64, nanos as u32);
+        let offset = Duration::from_secs(self.offset.unsigned_abs());
+        let timestamp = UNIX_EPOCH.checked_add(since_epoch).and_then(|timestamp| {
+            if self.offset < 0 {
+                timestamp.checked_sub(offset)
+            } else {
+                timestamp.checked_add(offset)
+            }
+        });
+        timestamp.ok_or_else(|| invalid("pcapng timestamp out of range"))
+    }
+}
+
+// Like `read_exact`, but returns `false` if the reader is at its end before reading anything.
+fn read_or_eof(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(len) => filled += len,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(true)
+}
+
+// Reads `len` bytes without trusting `len` enough to allocate it upfront.
+fn read_bytes(reader: &mut impl Read, len: u32) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(len.into()).read_to_end(&mut bytes)?;
+    if bytes.len() != len as usize {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+fn u16_at(bytes: &[u8], offset: usize, big_endian: bool) -> u16 {
+    let bytes = bytes[offset..offset + 2].try_into().unwrap();
+    if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    }
+}
+
+fn u32_at(bytes: &[u8], offset: usize, big_endian: bool) -> u32 {
+    let bytes = bytes[offset..offset + 4].try_into().unwrap();
+    if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn ethertype(packet: &[u8]) -> u16 {
+    match packet.first().map(|byte| byte >> 4) {
+        Some(4) => 0x0800,
+        Some(6) => 0x86dd,
+        _ => 0,
+    }
+}
+
+fn packet_type(direction: Direction) -> u16 {
+    match direction {
+        Direction::Incoming => PACKET_HOST,
+        Direction::Outgoing => PACKET_OUTGOING,
+    }
+}
+
+// The direction recorded in the packet type of a Linux cooked header. Packets addressed to this
+// host, to a broadcast or multicast group or to another host were all received.
+fn cooked_direction(link_type: LinkType, data: &[u8]) -> Option<Direction> {
+    let packet_type = match link_type {
+        LinkType::LinuxSll => u16::from_be_bytes(data.get(0..2)?.try_into().unwrap()),
+        LinkType::LinuxSll2 => (*data.get(10)?).into(),
+        LinkType::Ethernet | LinkType::Other(_) => return None,
+    };
+    match packet_type {
+        PACKET_OUTGOING => Some(Direction::Outgoing),
+        PACKET_HOST..=3 => Some(Direction::Incoming),
+        _ => None,
+    }
+}
+
+// https://www.tcpdump.org/linktypes/LINKTYPE_LINUX_SLL.html
+fn sll_header(packet: &[u8], direction: Direction) -> Vec<u8> {
+    let mut header = Vec::with_capacity(16);
+    header.extend_from_slice(&packet_type(direction).to_be_bytes());
+    header.extend_from_slice(&ARPHRD_ETHER.to_be_bytes());
+    header.extend_from_slice(&0u16.to_be_bytes()); // address length
+    header.extend_from_slice(&[0; 8]); // address
+    header.extend_from_slice(&ethertype(packet).to_be_bytes());
+    header
+}
+
+// https://www.tcpdump.org/linktypes/LINKTYPE_LINUX_SLL2.html
+fn sll2_header(packet: &[u8], direction: Direction) -> Vec<u8> {
+    let mut header = Vec::with_capacity(20);
+    header.extend_from_slice(&ethertype(packet).to_be_bytes());
+    header.extend_from_slice(&0u16.to_be_bytes()); // reserved
+    header.extend_from_slice(&0u32.to_be_bytes()); // interface index, unknown
+    header.extend_from_slice(&ARPHRD_ETHER.to_be_bytes());
+    header.push(packet_type(direction) as u8);
+    header.push(0); // address length
+    header.extend_from_slice(&[0; 8]); // address
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ETHERNET_FRAME: &[u8] = &[
+        0x02, 0, 0, 0, 0, 2, 0x02, 0, 0, 0, 0, 1, 0x86, 0xdd, 0x60, 0, 0, 0, 0, 0, 59, 64,
+    ];
+    const IPV4_PACKET: &[u8] = &[0x45, 0, 0, 20, 0, 0, 0x40, 0, 64, 253, 0, 0, 10, 0, 0, 1];
+    const IPV6_PACKET: &[u8] = &[0x60, 0, 0, 0, 0, 0, 59, 64, 0xfe, 0x80, 0, 0, 0, 0, 0];
+
+    fn write_capture(link_type: LinkType, frames: &[(SystemTime, &[u8], Direction)]) -> Vec<u8> {
+        let mut writer = PcapWriter::new(Vec::new(), link_type).unwrap();
+        for &(timestamp, frame, direction) in frames {
+            writer.write_frame_at(timestamp, frame, direction).unwrap();
+        }
+        writer.into_inner()
+    }
+
+    // Turns a capture written by `PcapWriter` into one written on a big-endian machine.
+    fn to_big_endian(mut capture: Vec<u8>) -> Vec<u8> {
+        let swap = |bytes: &mut [u8], fields: &[(usize, usize)]| {
+            for &(offset, size) in fields {
+                bytes[offset..offset + size].reverse();
+            }
+        };
+        swap(
+            &mut capture[..24],
+            &[(0, 4), (4, 2), (6, 2), (8, 4), (12, 4), (16, 4), (20, 4)],
+        );
+        let mut offset = 24;
+        while offset < capture.len() {
+            let header = &mut capture[offset..offset + 16];
+            swap(header, &[(0, 4), (4, 4), (8, 4), (12, 4)]);
+            offset += 16 + u32::from_be_bytes(header[8..12].try_into().unwrap()) as usize;
+        }
+        capture
+    }
+
+    fn read_capture(capture: &[u8]) -> io::Result<Vec<Record>> {
+        PcapReader::new(capture)?.collect()
+    }
+
+    #[test]
+    fn pcap_round_trip() {
+        let first = UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789);
+        let second = first + Duration::from_millis(1500);
+        for link_type in [LinkType::Ethernet, LinkType::LinuxSll, LinkType::LinuxSll2] {
+            let frames: &[(SystemTime, &[u8], Direction)] = match link_type {
+                LinkType::Ethernet => &[
+                    (first, ETHERNET_FRAME, Direction::Incoming),
+                    (second, ETHERNET_FRAME, Direction::Outgoing),
+                ],
+                _ => &[
+                    (first, IPV6_PACKET, Direction::Incoming),
+                    (second, IPV4_PACKET, Direction::Outgoing),
+                ],
+            };
+            let capture = write_capture(link_type, frames);
+            for capture in [capture.clone(), to_big_endian(capture)] {
+                let records = read_capture(&capture).unwrap();
+                assert_eq!(records.len(), frames.len());
+                for (record, &(timestamp, frame, direction)) in records.iter().zip(frames) {
+                    assert_eq!(record.timestamp, timestamp);
+                    assert_eq!(record.link_type, link_type);
+                    let (cooked, packet) = record.data.split_at(link_type.cooked_header_len());
+                    assert_eq!(packet, frame);
+                    match link_type {
+                        LinkType::Ethernet => assert_eq!(record.direction, None),
+                        _ => assert_eq!(record.direction, Some(direction)),
+                    }
+                    let protocol = match link_type {
+                        LinkType::LinuxSll => &cooked[14..16],
+                        LinkType::LinuxSll2 => &cooked[0..2],
+                        _ => continue,
+                    };
+                    assert_eq!(protocol, ethertype(frame).to_be_bytes());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pcap_with_microseconds() {
+        let mut capture = write_capture(
+            LinkType::Ethernet,
+            &[(UNIX_EPOCH, ETHERNET_FRAME, Direction::Incoming)],
+        );
+        capture[..4].copy_from_slice(&MAGIC_MICROS.to_le_bytes());
+        capture[28..32].copy_from_slice(&250u32.to_le_bytes());
+        let records = read_capture(&capture).unwrap();
+        assert_eq!(
+            records[0].timestamp,
+            UNIX_EPOCH + Duration::from_micros(250)
+        );
+    }
+
+    #[test]
+    fn not_a_capture() {
+        let err = read_capture(b"GET / HTTP/1.1\r\n\r\n").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    // Builds pcapng files block by block, in the byte order of the current section.
+    #[derive(Default)]
+    struct Pcapng {
+        bytes: Vec<u8>,
+        big_endian: bool,
+    }
+
+    impl Pcapng {
+        fn u16(&self, value: u16) -> [u8; 2] {
+            if self.big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            }
+        }
+
+        fn u32(&self, value: u32) -> [u8; 4] {
+            if self.big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            }
+        }
+
+        fn i64(&self, value: i64) -> [u8; 8] {
+            if self.big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            }
+        }
+
+        fn block(&mut self, block_type: u32, mut body: Vec<u8>) {
+            body.resize(body.len().next_multiple_of(4), 0);
+            let length = self.u32(12 + body.len() as u32);
+            self.bytes.extend(self.u32(block_type));
+            self.bytes.extend(length);
+            self.bytes.extend(body);
+            self.bytes.extend(length);
+        }
+
+        fn options(&self, body: &mut Vec<u8>, options: &[(u16, &[u8])]) {
+            for &(code, value) in options {
+                body.extend(self.u16(code));
+                body.extend(self.u16(value.len() as u16));
+                body.extend(value);
+                body.resize(body.len().next_multiple_of(4), 0);
+            }
+            if !options.is_empty() {
+                body.extend([0; 4]); // opt_endofopt
+            }
+        }
+
+        fn section(&mut self, big_endian: bool) {
+            self.big_endian = big_endian;
+            let mut body = self.u32(PCAPNG_BYTE_ORDER_MAGIC).to_vec();
+            body.extend(self.u16(1)); // version 1.0
+            body.extend(self.u16(0));
+            body.extend([0xff; 8]); // unknown section length
+            self.block(PCAPNG_SECTION_HEADER, body);
+        }
+
+        fn interface(&mut self, link_type: LinkType, options: &[(u16, &[u8])]) {
+            let mut body = self.u16(link_type.code() as u16).to_vec();
+            body.extend([0; 2]);
+            body.extend(self.u32(SNAPLEN));
+            self.options(&mut body, options);
+            self.block(PCAPNG_INTERFACE_DESCRIPTION, body);
+        }
+
+        fn packet(&mut self, interface: u32, units: u64, data: &[u8], options: &[(u16, &[u8])]) {
+            let mut body = self.u32(interface).to_vec();
+            body.extend(self.u32((units >> 32) as u32));
+            body.extend(self.u32(units as u32));
+            body.extend(self.u32(data.len() as u32)); // captured length
+            body.extend(self.u32(data.len() as u32)); // original length
+            body.extend(data);
+            body.resize(body.len().next_multiple_of(4), 0);
+            self.options(&mut body, options);
+            self.block(PCAPNG_ENHANCED_PACKET, body);
+        }
+
+        fn simple_packet(&mut self, data: &[u8]) {
+            let mut body = self.u32(data.len() as u32).to_vec();
+            body.extend(data);
+            self.block(PCAPNG_SIMPLE_PACKET, body);
+        }
+    }
+
+    #[test]
+    fn pcapng_sections_and_interfaces() {
+        let mut pcapng = Pcapng::default();
+        pcapng.section(false);
+        // Nanoseconds, shifted by a million seconds.
+        pcapng.interface(
+            LinkType::Ethernet,
+            &[(9, &[9]), (14, &pcapng.i64(1_000_000))],
+        );
+        pcapng.interface(LinkType::LinuxSll, &[]); // microseconds
+        pcapng.block(5, vec![0; 20]); // an interface statistics block
+        pcapng.packet(
+            0,
+            1_500_000_000_250,
+            ETHERNET_FRAME,
+            &[(1, b"a comment"), (PCAPNG_EPB_FLAGS, &pcapng.u32(2))],
+        );
+        let mut cooked = sll_header(IPV6_PACKET, Direction::Outgoing);
+        cooked.extend(IPV6_PACKET);
+        pcapng.packet(1, 2_000_001, &cooked, &[]);
+        pcapng.simple_packet(ETHERNET_FRAME);
+        // A big-endian section with 1/64 seconds, shifted back by 10 seconds.
+        pcapng.section(true);
+        pcapng.interface(LinkType::Ethernet, &[(9, &[0x86]), (14, &pcapng.i64(-10))]);
+        pcapng.packet(0, 64 * 100 + 32, ETHERNET_FRAME, &[(2, &pcapng.u32(1))]);
+        // The interfaces of the first section are gone.
+        pcapng.packet(1, 0, ETHERNET_FRAME, &[]);
+
+        let mut reader = PcapReader::new(&pcapng.bytes[..]).unwrap();
+        let mut next = || reader.next_record().unwrap().unwrap();
+        assert_eq!(
+            next(),
+            Record {
+                timestamp: UNIX_EPOCH + Duration::new(1_001_500, 250),
+                link_type: LinkType::Ethernet,
+                data: ETHERNET_FRAME.to_vec(),
+                direction: Some(Direction::Outgoing),
+            }
+        );
+        assert_eq!(
+            next(),
+            Record {
+                timestamp: UNIX_EPOCH + Duration::new(2, 1000),
+                link_type: LinkType::LinuxSll,
+                data: cooked,
+                direction: Some(Direction::Outgoing),
+            }
+        );
+        assert_eq!(
+            next(),
+            Record {
+                timestamp: UNIX_EPOCH,
+                link_type: LinkType::Ethernet,
+                data: ETHERNET_FRAME.to_vec(),
+                direction: None,
+            }
+        );
+        assert_eq!(
+            next(),
+            Record {
+                timestamp: UNIX_EPOCH + Duration::from_millis(90_500),
+                link_type: LinkType::Ethernet,
+                data: ETHERNET_FRAME.to_vec(),
+                direction: Some(Direction::Incoming),
+            }
+        );
+        let err = reader.next_record().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn pcapng_timestamp_out_of_range() {
+        let mut pcapng = Pcapng::default();
+        pcapng.section(false);
+        pcapng.interface(LinkType::Ethernet, &[(9, &[0])]); // seconds
+        pcapng.packet(0, u64::MAX, ETHERNET_FRAME, &[]);
+        let err = read_capture(&pcapng.bytes).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,784 @@
+// Packet sockets: Rust bindings of libraw's raw.h
+//
+// Copyright © 2023 Josef Schönberger
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included
+// in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES
+// OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.
+// IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT,
+// TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
+// OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+#![allow(non_camel_case_types)] // Type names are given in C
+
+#[cfg(not(feature = "pure-rust"))]
+use std::ffi::c_void;
+use std::ffi::CString;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::io::{self, Write};
+use std::mem::ManuallyDrop;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, RawFd};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "mio")]
+use mio::unix::SourceFd;
+use nix::errno::Errno;
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+
+#[cfg(doc)]
+use crate::pcap::LinkType;
+use crate::pcap::{Direction, PcapWriter};
+
+// ----------------------- raw.h ----------------------
+
+// build.rs compiles libraw and tells cargo to link it statically.
+#[cfg(not(feature = "pure-rust"))]
+extern "C" {
+    fn grnvs_open(ifname: *const i8, layer: i32) -> i32;
+    fn grnvs_read(fd: i32, buf: *const c_void, maxlen: usize, timeout: *mut i32) -> isize;
+    fn grnvs_write(fd: i32, buf: *const c_void, maxlen: usize) -> isize;
+    fn grnvs_close(fd: i32) -> i32;
+    fn grnvs_get_hwaddr(fd: i32) -> *const [u8; 6];
+    fn grnvs_get_ipaddr(fd: i32) -> in_addr;
+    fn grnvs_get_ip6addr(fd: i32) -> *const [u8; 16];
+}
+
+// With the "pure-rust" feature, the same functions are provided by `native` instead.
+#[cfg(feature = "pure-rust")]
+mod native;
+#[cfg(feature = "pure-rust")]
+use native::*;
+
+// Unfortunately, this is needed as a proxy struct since [u8; 4] returning directly is not FFI-safe.
+#[repr(C, packed)]
+struct in_addr {
+    addr: [u8; 4],
+}
+
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    SOCK_DGRAM = 2,
+    SOCK_RAW = 3,
+}
+
+/// A packet socket opened through libraw.
+///
+/// `Socket` is `Send` and `Sync`: reading and writing only issue system calls on the file
+/// descriptor, and its [tap](Socket::set_tap) is guarded by a mutex. Use [`Socket::split`] to read
+/// in one thread while writing in another.
+pub struct Socket {
+    fd: i32,
+    tap: Mutex<TapState>,
+}
+
+/// A [`PcapWriter`] recording the frames of a [`Socket`], see [`Socket::set_tap`].
+pub type Tap = PcapWriter<Box<dyn Write + Send>>;
+
+// The tap of a socket, and why the last one was removed, if writing to it failed.
+#[derive(Default)]
+struct TapState {
+    writer: Option<Tap>,
+    error: Option<io::Error>,
+}
+
+/// The result of a successful [`Socket::read`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadOutcome {
+    /// A frame of the given length was written into the buffer. Frames longer than the buffer are
+    /// truncated. Packet sockets have no end-of-file, so a length of 0 only ever means that an
+    /// empty frame was received.
+    Frame(usize),
+    /// The timeout expired before a frame arrived.
+    TimedOut,
+}
+
+impl Socket {
+    #[inline]
+    fn with_fd(fd: i32) -> Self {
+        Socket {
+            fd,
+            tap: Mutex::default(),
+        }
+    }
+
+    /// Opens a packet socket on the interface `ifname`.
+    ///
+    /// Fails if the name cannot be passed to libraw, if there is no such interface, or if the
+    /// process is not allowed to open raw sockets (it needs `CAP_NET_RAW`).
+    pub fn open(ifname: &str, layer: Layer) -> Result<Self, Error> {
+        let invalid = || Error::InvalidInterfaceName(ifname.to_string());
+        if ifname.is_empty() || ifname.len() >= IFNAMSIZ {
+            return Err(invalid());
+        }
+        let c = CString::new(ifname).map_err(|_| invalid())?;
+        let fd = unsafe { grnvs_open(c.as_ptr(), layer as i32) };
+        if fd >= 0 {
+            return Ok(Socket::with_fd(fd));
+        }
+        Err(match Errno::last() {
+            errno @ (Errno::ENODEV | Errno::ENXIO) => {
+                Error::InterfaceNotFound(ifname.to_string(), errno)
+            }
+            errno @ (Errno::EPERM | Errno::EACCES) => {
+                Error::PermissionDenied(ifname.to_string(), errno)
+            }
+            errno => Error::Os {
+                operation: Operation::Open,
+                errno,
+                context: Some(ifname.to_string()),
+            },
+        })
+    }
+
+    /// Read the given amount of bytes from the Socket. You may optionally choose to provide a
+    /// timeout argument (timeout in milliseconds). libraw decrements it by the time spent waiting,
+    /// so it still holds the remaining timeout after the call returns.
+    ///
+    /// Returns the amount of bytes that were actually read, or [`ReadOutcome::TimedOut`] if no
+    /// frame arrived in time. In non-blocking mode, a read without a timeout reports an empty
+    /// queue as an error for which [`Error::is_would_block`] holds.
+    #[inline]
+    pub fn read(
+        &mut self,
+        destination: &mut [u8],
+        timeout: Option<&mut i32>,
+    ) -> Result<ReadOutcome, Error> {
+        self.read_shared(destination, timeout)
+    }
+
+    // `read` and `write` take `&mut self` so that a socket is not used from two places at once by
+    // accident. Wrappers that synchronize access themselves use these shared variants instead.
+    pub(crate) fn read_shared(
+        &self,
+        destination: &mut [u8],
+        timeout: Option<&mut i32>,
+    ) -> Result<ReadOutcome, Error> {
+        let mut timeout = timeout;
+        let result = unsafe {
+            grnvs_read(
+                self.fd,
+                destination.as_mut_ptr() as _,
+                destination.len(),
+                timeout
+                    .as_deref_mut()
+                    .map(|r| r as *mut i32)
+                    .unwrap_or(std::ptr::null_mut()),
+            )
+        };
+        if result < 0 {
+            Err(Error::last(Operation::Read))
+        } else if result == 0 && matches!(timeout, Some(remaining) if *remaining <= 0) {
+            Ok(ReadOutcome::TimedOut)
+        } else {
+            self.record(&destination[..result as usize], Direction::Incoming);
+            Ok(ReadOutcome::Frame(result as _))
+        }
+    }
+
+    /// Like [`Socket::read`], but waits at most `timeout` for a frame to arrive.
+    #[inline]
+    pub fn read_timeout(
+        &mut self,
+        destination: &mut [u8],
+        timeout: Duration,
+    ) -> Result<ReadOutcome, Error> {
+        self.read_shared(destination, Some(&mut timeout_millis(timeout)))
+    }
+
+    /// Like [`Socket::read`], but gives up once `deadline` has passed.
+    ///
+    /// Passing the same deadline to every call lets a loop that skips unrelated frames wait for a
+    /// total amount of time without any bookkeeping.
+    #[inline]
+    pub fn read_deadline(
+        &mut self,
+        destination: &mut [u8],
+        deadline: Instant,
+    ) -> Result<ReadOutcome, Error> {
+        match remaining(deadline) {
+            Some(timeout) => self.read_timeout(destination, timeout),
+            None => Ok(ReadOutcome::TimedOut),
+        }
+    }
+
+    /// Writes the given amount of bytes into the Socket.
+    ///
+    /// Returns the amount of bytes that were actually read if no error occured.
+    #[inline]
+    pub fn write(&mut self, source: &[u8]) -> Result<usize, Error> {
+        self.write_shared(source)
+    }
+
+    pub(crate) fn write_shared(&self, source: &[u8]) -> Result<usize, Error> {
+        let result = unsafe { grnvs_write(self.fd, source.as_ptr() as _, source.len()) };
+        if result < 0 {
+            Err(Error::last(Operation::Write))
+        } else {
+            self.record(&source[..result as usize], Direction::Outgoing);
+            Ok(result as _)
+        }
+    }
+
+    /// Records every frame read from or written to the socket from now on to `tap`, replacing the
+    /// previous tap. Create it with the link type [`LinkType::for_layer`] returns for the layer
+    /// the socket was opened with.
+    ///
+    /// libraw doesn't report the protocol of the packets a [`Layer::SOCK_DGRAM`] socket reads, so
+    /// the cooked headers of such captures only tell IPv4 and IPv6 apart by the IP version. Other
+    /// packets, e.g. ARP, are recorded with protocol 0, which Wireshark doesn't dissect further.
+    ///
+    /// Recording never makes reading or writing fail: if writing to the tap fails, the tap is
+    /// removed and the error is kept for [`Socket::take_tap_error`].
+    pub fn set_tap<W: Write + Send + 'static>(&self, tap: PcapWriter<W>) -> Option<Tap> {
+        let mut state = self.lock_tap();
+        state.error = None;
+        state.writer.replace(tap.boxed())
+    }
+
+    /// Stops recording and returns the tap, e.g. to flush it.
+    #[inline]
+    pub fn take_tap(&self) -> Option<Tap> {
+        self.lock_tap().writer.take()
+    }
+
+    /// Returns the error that made the socket remove its tap, if writing to the tap failed since
+    /// it was set.
+    #[inline]
+    pub fn take_tap_error(&self) -> Option<io::Error> {
+        self.lock_tap().error.take()
+    }
+
+    fn lock_tap(&self) -> MutexGuard<'_, TapState> {
+        self.tap.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn record(&self, frame: &[u8], direction: Direction) {
+        let mut state = self.lock_tap();
+        if let Some(writer) = state.writer.as_mut() {
+            if let Err(err) = writer.write_frame(frame, direction) {
+                state.writer = None;
+                state.error = Some(err);
+            }
+        }
+    }
+
+    // Gives up ownership of the fd without closing it.
+    fn into_fd(self) -> i32 {
+        let mut socket = ManuallyDrop::new(self);
+        socket
+            .tap
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .writer
+            .take();
+        socket.fd
+    }
+
+    /// Switches the socket into or out of non-blocking mode.
+    ///
+    /// In non-blocking mode, [`Socket::read`] without a timeout fails right away if no frame is
+    /// queued, and [`Socket::write`] fails if the send buffer is full. Both errors satisfy
+    /// [`Error::is_would_block`].
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<(), Error> {
+        let configure = |errno| Error::Os {
+            operation: Operation::Configure,
+            errno,
+            context: None,
+        };
+        let flags = fcntl(self.fd, FcntlArg::F_GETFL).map_err(configure)?;
+        let mut flags = OFlag::from_bits_truncate(flags);
+        flags.set(OFlag::O_NONBLOCK, nonblocking);
+        fcntl(self.fd, FcntlArg::F_SETFL(flags)).map_err(configure)?;
+        Ok(())
+    }
+
+    /// Splits the socket into a half for reading and a half for writing, which can be moved to
+    /// different threads. The socket is closed once both halves are dropped.
+    #[inline]
+    pub fn split(self) -> (SocketReader, SocketWriter) {
+        let socket = Arc::new(self);
+        (SocketReader(Arc::clone(&socket)), SocketWriter(socket))
+    }
+
+    /// Closes the socket, reporting errors that dropping it would silently ignore.
+    #[inline]
+    pub fn close(self) -> Result<(), Error> {
+        let result = unsafe { grnvs_close(self.into_fd()) };
+        if result < 0 {
+            Err(Error::last(Operation::Close))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the hardware address of the interface the socket was opened on.
+    #[inline]
+    pub fn get_hwaddr(&self) -> Result<MacAddr, Error> {
+        match unsafe { grnvs_get_hwaddr(self.fd).as_ref() } {
+            Some(hwaddr) => Ok(MacAddr(*hwaddr)),
+            None => Err(Error::Os {
+                operation: Operation::AddressLookup,
+                errno: Errno::EADDRNOTAVAIL,
+                context: Some("libraw knows no hardware address".to_string()),
+            }),
+        }
+    }
+
+    #[inline]
+    pub fn get_ipaddr(&self) -> Ipv4Addr {
+        unsafe { grnvs_get_ipaddr(self.fd).addr.into() }
+    }
+
+    #[inline]
+    pub fn get_ip6addr(&self) -> Ipv6Addr {
+        unsafe { (*grnvs_get_ip6addr(self.fd)).into() }
+    }
+}
+
+// libraw counts in milliseconds. Round up so we never give up before the timeout expired.
+fn timeout_millis(timeout: Duration) -> i32 {
+    i32::try_from(timeout.as_nanos().div_ceil(1_000_000)).unwrap_or(i32::MAX)
+}
+
+// The time left until `deadline`, or `None` if it has passed.
+fn remaining(deadline: Instant) -> Option<Duration> {
+    deadline
+        .checked_duration_since(Instant::now())
+        .filter(|remaining| !remaining.is_zero())
+}
+
+impl Drop for Socket {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { grnvs_close(self.fd) };
+    }
+}
+
+impl AsRawFd for Socket {
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl AsFd for Socket {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        unsafe { BorrowedFd::borrow_raw(self.fd) }
+    }
+}
+
+#[cfg(feature = "mio")]
+impl mio::event::Source for Socket {
+    fn register(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> io::Result<()> {
+        SourceFd(&self.fd).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> io::Result<()> {
+        SourceFd(&self.fd).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &mio::Registry) -> io::Result<()> {
+        SourceFd(&self.fd).deregister(registry)
+    }
+}
+
+impl IntoRawFd for Socket {
+    /// Gives up ownership of the file descriptor without closing it.
+    ///
+    /// libraw still remembers the socket's interface. Pass the descriptor back to
+    /// [`Socket::from_raw_fd`] to close it properly; `close(2)` alone skips libraw's cleanup.
+    #[inline]
+    fn into_raw_fd(self) -> RawFd {
+        self.into_fd()
+    }
+}
+
+impl FromRawFd for Socket {
+    /// Takes ownership of `fd`, closing it through libraw when dropped.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an open packet socket that nothing else owns. The address getters only work
+    /// for sockets that were opened with [`Socket::open`], e.g. descriptors returned by
+    /// [`Socket::into_raw_fd`].
+    #[inline]
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        Socket::with_fd(fd)
+    }
+}
+
+/// The reading half of a [`Socket`], see [`Socket::split`].
+pub struct SocketReader(Arc<Socket>);
+
+impl SocketReader {
+    /// See [`Socket::read`].
+    #[inline]
+    pub fn read(
+        &mut self,
+        destination: &mut [u8],
+        timeout: Option<&mut i32>,
+    ) -> Result<ReadOutcome, Error> {
+        self.0.read_shared(destination, timeout)
+    }
+
+    /// See [`Socket::read_timeout`].
+    #[inline]
+    pub fn read_timeout(
+        &mut self,
+        destination: &mut [u8],
+        timeout: Duration,
+    ) -> Result<ReadOutcome, Error> {
+        self.0
+            .read_shared(destination, Some(&mut timeout_millis(timeout)))
+    }
+
+    /// See [`Socket::read_deadline`].
+    #[inline]
+    pub fn read_deadline(
+        &mut self,
+        destination: &mut [u8],
+        deadline: Instant,
+    ) -> Result<ReadOutcome, Error> {
+        match remaining(deadline) {
+            Some(timeout) => self.read_timeout(destination, timeout),
+            None => Ok(ReadOutcome::TimedOut),
+        }
+    }
+
+    /// The shared socket, e.g. to look up its addresses.
+    #[inline]
+    pub fn socket(&self) -> &Socket {
+        &self.0
+    }
+}
+
+/// The writing half of a [`Socket`], see [`Socket::split`].
+pub struct SocketWriter(Arc<Socket>);
+
+impl SocketWriter {
+    /// See [`Socket::write`].
+    #[inline]
+    pub fn write(&mut self, source: &[u8]) -> Result<usize, Error> {
+        self.0.write_shared(source)
+    }
+
+    /// The shared socket, e.g. to look up its addresses.
+    #[inline]
+    pub fn socket(&self) -> &Socket {
+        &self.0
+    }
+}
+
+/// A MAC (Ethernet hardware) address, displayed as `aa:bb:cc:dd:ee:ff`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MacAddr(pub [u8; 6]);
+
+impl MacAddr {
+    pub const BROADCAST: MacAddr = MacAddr([0xff; 6]);
+
+    #[inline]
+    pub const fn new(a: u8, b: u8, c: u8, d: u8, e: u8, f: u8) -> Self {
+        MacAddr([a, b, c, d, e, f])
+    }
+
+    #[inline]
+    pub const fn octets(&self) -> [u8; 6] {
+        self.0
+    }
+
+    #[inline]
+    pub fn is_broadcast(&self) -> bool {
+        *self == Self::BROADCAST
+    }
+
+    /// Whether this is a group address (including the broadcast address).
+    #[inline]
+    pub fn is_multicast(&self) -> bool {
+        self.0[0] & 0x01 != 0
+    }
+
+    #[inline]
+    pub fn is_unicast(&self) -> bool {
+        !self.is_multicast()
+    }
+
+    /// Whether the address was assigned locally instead of by the manufacturer (the U/L bit).
+    #[inline]
+    pub fn is_locally_administered(&self) -> bool {
+        self.0[0] & 0x02 != 0
+    }
+}
+
+impl From<[u8; 6]> for MacAddr {
+    #[inline]
+    fn from(octets: [u8; 6]) -> Self {
+        MacAddr(octets)
+    }
+}
+
+impl From<MacAddr> for [u8; 6] {
+    #[inline]
+    fn from(addr: MacAddr) -> Self {
+        addr.0
+    }
+}
+
+impl Display for MacAddr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            a, b, c, d, e, g
+        )
+    }
+}
+
+/// Parses `aa:bb:cc:dd:ee:ff`; dashes are accepted as separators, too, but not mixed with colons.
+impl FromStr for MacAddr {
+    type Err = ParseMacAddrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut octets = [0; 6];
+        // All separators must be the same.
+        let separator = if s.contains('-') { '-' } else { ':' };
+        let mut parts = s.split(separator);
+        for octet in &mut octets {
+            let part = parts.next().ok_or(ParseMacAddrError)?;
+            // `from_str_radix` would accept a sign, e.g. "+a".
+            if part.len() != 2 || !part.bytes().all(|c| c.is_ascii_hexdigit()) {
+                return Err(ParseMacAddrError);
+            }
+            *octet = u8::from_str_radix(part, 16).map_err(|_| ParseMacAddrError)?;
+        }
+        match parts.next() {
+            None => Ok(MacAddr(octets)),
+            Some(_) => Err(ParseMacAddrError),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseMacAddrError;
+
+impl Display for ParseMacAddrError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid MAC address syntax")
+    }
+}
+
+impl std::error::Error for ParseMacAddrError {}
+
+// Interface names (including the terminating NUL byte) must fit into this many bytes, see
+// netdevice(7).
+const IFNAMSIZ: usize = 16;
+
+/// The libraw call during which an [`Error`] occured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Open,
+    Read,
+    Write,
+    Close,
+    AddressLookup,
+    /// Changing the socket's file status flags, see [`Socket::set_nonblocking`].
+    Configure,
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operation::Open => "opening",
+            Operation::Read => "reading from",
+            Operation::Write => "writing to",
+            Operation::Close => "closing",
+            Operation::AddressLookup => "looking up the address of",
+            Operation::Configure => "configuring",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The interface name is empty, too long, or contains a NUL byte.
+    InvalidInterfaceName(String),
+    /// There is no interface with the given name. Holds the `errno` the kernel reported.
+    InterfaceNotFound(String, Errno),
+    /// Opening a packet socket requires `CAP_NET_RAW` (or root). Holds the `errno` the kernel
+    /// reported.
+    PermissionDenied(String, Errno),
+    /// Any other error reported by the operating system.
+    Os {
+        operation: Operation,
+        errno: Errno,
+        context: Option<String>,
+    },
+}
+
+impl Error {
+    /// Creates an [`Error::Os`] from the current value of `errno`.
+    fn last(operation: Operation) -> Self {
+        Error::Os {
+            operation,
+            errno: Errno::last(),
+            context: None,
+        }
+    }
+
+    /// The operation that failed.
+    pub fn operation(&self) -> Operation {
+        match self {
+            Error::InvalidInterfaceName(_)
+            | Error::InterfaceNotFound(..)
+            | Error::PermissionDenied(..) => Operation::Open,
+            Error::Os { operation, .. } => *operation,
+        }
+    }
+
+    /// The `errno` value describing the error, if the operating system reported one.
+    pub fn errno(&self) -> Option<Errno> {
+        match self {
+            Error::InvalidInterfaceName(_) => None,
+            Error::InterfaceNotFound(_, errno)
+            | Error::PermissionDenied(_, errno)
+            | Error::Os { errno, .. } => Some(*errno),
+        }
+    }
+
+    /// Whether the operation failed only because the socket is in non-blocking mode and would
+    /// have had to wait.
+    #[inline]
+    pub fn is_would_block(&self) -> bool {
+        self.errno() == Some(Errno::EAGAIN)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidInterfaceName(ifname) => write!(f, "Invalid interface name {:?}", ifname),
+            Error::InterfaceNotFound(ifname, _) => write!(f, "No such interface: {}", ifname),
+            Error::PermissionDenied(ifname, _) => write!(
+                f,
+                "Permission denied while opening {} (missing CAP_NET_RAW?)",
+                ifname
+            ),
+            Error::Os {
+                operation,
+                errno,
+                context: None,
+            } => write!(f, "Error while {} socket: {}", operation, errno),
+            Error::Os {
+                operation,
+                errno,
+                context: Some(context),
+            } => write!(
+                f,
+                "Error while {} socket ({}): {}",
+                operation, context, errno
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::InterfaceNotFound(_, errno)
+            | Error::PermissionDenied(_, errno)
+            | Error::Os { errno, .. } => Some(errno),
+            Error::InvalidInterfaceName(_) => None,
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        let kind = match &err {
+            Error::InvalidInterfaceName(_) => io::ErrorKind::InvalidInput,
+            Error::InterfaceNotFound(..) => io::ErrorKind::NotFound,
+            Error::PermissionDenied(..) => io::ErrorKind::PermissionDenied,
+            Error::Os { errno, .. } => io::Error::from_raw_os_error(*errno as i32).kind(),
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mac_addr_display() {
+        let mac = MacAddr::new(0x02, 0x00, 0x5e, 0x10, 0xab, 0xcd);
+        assert_eq!(mac.to_string(), "02:00:5e:10:ab:cd");
+        assert_eq!(MacAddr::BROADCAST.to_string(), "ff:ff:ff:ff:ff:ff");
+    }
+
+    #[test]
+    fn mac_addr_from_str() {
+        let mac = MacAddr::new(0x02, 0x00, 0x5e, 0x10, 0xab, 0xcd);
+        assert_eq!("02:00:5e:10:ab:cd".parse(), Ok(mac));
+        assert_eq!("02-00-5E-10-AB-CD".parse(), Ok(mac));
+        for invalid in [
+            "",
+            "02:00:5e:10:ab",
+            "02:00:5e:10:ab:cd:ef",
+            "02:00:5e:10:ab:",
+            "2:00:5e:10:ab:cd",
+            "002:00:5e:10:ab:cd",
+            "+a:bb:cc:dd:ee:ff",
+            "aa-bb:cc-dd:ee:ff",
+            "0x:00:5e:10:ab:cd",
+            "02 00 5e 10 ab cd",
+        ] {
+            assert_eq!(
+                invalid.parse::<MacAddr>(),
+                Err(ParseMacAddrError),
+                "{invalid:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn mac_addr_kinds() {
+        assert!(MacAddr::BROADCAST.is_broadcast());
+        assert!(MacAddr::BROADCAST.is_multicast());
+
+        let multicast = MacAddr::new(0x33, 0x33, 0, 0, 0, 1);
+        assert!(multicast.is_multicast() && !multicast.is_unicast());
+        assert!(!multicast.is_broadcast());
+
+        let local = MacAddr::new(0x02, 0, 0, 0, 0, 1);
+        assert!(local.is_unicast() && local.is_locally_administered());
+
+        let universal = MacAddr::new(0x00, 0x1b, 0x21, 0x12, 0x34, 0x56);
+        assert!(universal.is_unicast() && !universal.is_locally_administered());
+    }
+}
@@ -0,0 +1,203 @@
+// Packet sockets without libraw: a pure Rust implementation of raw.h
+//
+// Copyright © 2023 Josef Schönberger
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included
+// in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES
+// OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.
+// IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT,
+// TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
+// OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+// A drop-in replacement for the raw.h part of libraw. The functions keep the C signatures and
+// report errors through `errno` like their C counterparts, so `Socket` does not need to know which
+// backend it is talking to.
+
+use super::in_addr;
+use nix::libc;
+use std::ffi::{c_void, CStr};
+use std::sync::Mutex;
+use std::time::Instant;
+
+// libraw looks up the addresses of the interface once when opening a socket and hands out
+// pointers to them until the socket is closed. The boxes keep these pointers stable.
+struct Interface {
+    fd: i32,
+    hwaddr: Box<[u8; 6]>,
+    ipaddr: [u8; 4],
+    ip6addr: Box<[u8; 16]>,
+}
+
+static INTERFACES: Mutex<Vec<Interface>> = Mutex::new(Vec::new());
+static UNSPECIFIED_IP6ADDR: [u8; 16] = [0; 16];
+
+fn interfaces() -> std::sync::MutexGuard<'static, Vec<Interface>> {
+    INTERFACES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+unsafe fn set_errno(errno: i32) {
+    *libc::__errno_location() = errno;
+}
+
+pub unsafe fn grnvs_open(ifname: *const i8, layer: i32) -> i32 {
+    let ifname = CStr::from_ptr(ifname);
+    let ifindex = libc::if_nametoindex(ifname.as_ptr());
+    if ifindex == 0 {
+        return -1;
+    }
+
+    let protocol = (libc::ETH_P_ALL as u16).to_be();
+    let fd = libc::socket(libc::AF_PACKET, layer, protocol as i32);
+    if fd < 0 {
+        return -1;
+    }
+
+    let mut addr: libc::sockaddr_ll = std::mem::zeroed();
+    addr.sll_family = libc::AF_PACKET as u16;
+    addr.sll_protocol = protocol;
+    addr.sll_ifindex = ifindex as i32;
+    let result = libc::bind(
+        fd,
+        &addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+        std::mem::size_of::<libc::sockaddr_ll>() as u32,
+    );
+    if result < 0 {
+        let errno = *libc::__errno_location();
+        libc::close(fd);
+        set_errno(errno);
+        return -1;
+    }
+
+    let interface = lookup_addresses(fd, ifname);
+    let mut interfaces = interfaces();
+    // The descriptor may be reused from a socket that was closed without grnvs_close, e.g. after
+    // `Socket::into_fd`; its addresses are stale.
+    interfaces.retain(|interface| interface.fd != fd);
+    interfaces.push(interface);
+    fd
+}
+
+unsafe fn lookup_addresses(fd: i32, ifname: &CStr) -> Interface {
+    let mut interface = Interface {
+        fd,
+        hwaddr: Box::new([0; 6]),
+        ipaddr: [0; 4],
+        ip6addr: Box::new([0; 16]),
+    };
+
+    let mut ifaddrs = std::ptr::null_mut();
+    if libc::getifaddrs(&mut ifaddrs) < 0 {
+        return interface;
+    }
+
+    // Prefer the first IPv4 address and the first IPv6 address that is not link-local.
+    let mut have_ipaddr = false;
+    let mut have_global_ip6addr = false;
+    let mut link_local_ip6addr = None;
+    let mut current = ifaddrs;
+    while let Some(ifaddr) = current.as_ref() {
+        current = ifaddr.ifa_next;
+        if ifaddr.ifa_addr.is_null() || CStr::from_ptr(ifaddr.ifa_name) != ifname {
+            continue;
+        }
+        match (*ifaddr.ifa_addr).sa_family as i32 {
+            libc::AF_PACKET => {
+                let ll = &*(ifaddr.ifa_addr as *const libc::sockaddr_ll);
+                interface.hwaddr.copy_from_slice(&ll.sll_addr[..6]);
+            }
+            libc::AF_INET if !have_ipaddr => {
+                let sin = &*(ifaddr.ifa_addr as *const libc::sockaddr_in);
+                interface.ipaddr = sin.sin_addr.s_addr.to_ne_bytes();
+                have_ipaddr = true;
+            }
+            libc::AF_INET6 if !have_global_ip6addr => {
+                let sin6 = &*(ifaddr.ifa_addr as *const libc::sockaddr_in6);
+                let addr = sin6.sin6_addr.s6_addr;
+                if addr[0] == 0xfe && addr[1] & 0xc0 == 0x80 {
+                    link_local_ip6addr.get_or_insert(addr);
+                } else {
+                    *interface.ip6addr = addr;
+                    have_global_ip6addr = true;
+                }
+            }
+            _ => {}
+        }
+    }
+    libc::freeifaddrs(ifaddrs);
+
+    if let (false, Some(addr)) = (have_global_ip6addr, link_local_ip6addr) {
+        *interface.ip6addr = addr;
+    }
+    interface
+}
+
+pub unsafe fn grnvs_read(fd: i32, buf: *const c_void, maxlen: usize, timeout: *mut i32) -> isize {
+    // Like libraw, wait for the socket to become readable and subtract the time spent waiting
+    // from the timeout. A negative timeout waits forever.
+    if let Some(timeout) = timeout.as_mut() {
+        let mut pollfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let start = Instant::now();
+        let ready = libc::poll(&mut pollfd, 1, *timeout);
+        if *timeout >= 0 {
+            let elapsed = i32::try_from(start.elapsed().as_millis()).unwrap_or(i32::MAX);
+            *timeout = (*timeout - elapsed).max(0);
+        }
+        if ready < 0 {
+            return -1;
+        } else if ready == 0 {
+            *timeout = 0;
+            return 0;
+        }
+    }
+    libc::read(fd, buf as *mut c_void, maxlen)
+}
+
+pub unsafe fn grnvs_write(fd: i32, buf: *const c_void, maxlen: usize) -> isize {
+    libc::write(fd, buf, maxlen)
+}
+
+pub unsafe fn grnvs_close(fd: i32) -> i32 {
+    interfaces().retain(|interface| interface.fd != fd);
+    libc::close(fd)
+}
+
+pub unsafe fn grnvs_get_hwaddr(fd: i32) -> *const [u8; 6] {
+    interfaces()
+        .iter()
+        .find(|interface| interface.fd == fd)
+        .map_or(std::ptr::null(), |interface| &*interface.hwaddr as *const _)
+}
+
+pub unsafe fn grnvs_get_ipaddr(fd: i32) -> in_addr {
+    let addr = interfaces()
+        .iter()
+        .find(|interface| interface.fd == fd)
+        .map_or([0; 4], |interface| interface.ipaddr);
+    in_addr { addr }
+}
+
+pub unsafe fn grnvs_get_ip6addr(fd: i32) -> *const [u8; 16] {
+    interfaces()
+        .iter()
+        .find(|interface| interface.fd == fd)
+        .map_or(&UNSPECIFIED_IP6ADDR as *const _, |interface| {
+            &*interface.ip6addr as *const _
+        })
+}
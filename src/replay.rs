@@ -0,0 +1,310 @@
+// Replaying captures: a PacketIo backend that feeds recorded traffic to protocol code
+//
+// Copyright © 2023 Josef Schönberger
+//
+// Permission is hereby granted, free of charge, to any person obtaining
+// a copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included
+// in all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND,
+// EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES
+// OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.
+// IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+// DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT,
+// TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE
+// OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use nix::errno::Errno;
+
+use crate::packet_io::{address_setters, Addresses, PacketIo};
+use crate::pcap::{Direction, LinkType, PcapReader, Record};
+use crate::raw::{Error, MacAddr, Operation, ReadOutcome};
+
+/// Feeds the frames of a capture to code written against [`PacketIo`] and collects the frames it
+/// sends, so a recorded exchange can drive it deterministically.
+///
+/// Frames are received in the order of the capture. Frames that the recording host sent rather
+/// than received are skipped; compare them with [`Replay::sent`] instead. They are those the
+/// capture marks as [outgoing](Direction::Outgoing) and, since Ethernet captures like those of a
+/// [`Layer::SOCK_RAW`](crate::Layer::SOCK_RAW) tap don't record a direction, Ethernet frames from
+/// the [hardware address](Replay::set_hwaddr) of the replay. Linux cooked headers are stripped, so
+/// captures of [`Layer::SOCK_DGRAM`](crate::Layer::SOCK_DGRAM) sockets yield the packets such a
+/// socket reads; all other frames are received as they were captured. Once the capture is
+/// exhausted, [`PacketIo::recv`] times out right away, or fails with `ENODATA` without a timeout
+/// instead of waiting forever.
+///
+/// Unless set otherwise, it has the addresses of the first end of a
+/// [`MemorySocket`](crate::MemorySocket) pair.
+pub struct Replay {
+    records: VecDeque<Record>,
+    timing: bool,
+    // When the first frame was received, and when it was captured.
+    start: Option<(Instant, SystemTime)>,
+    sent: Vec<Vec<u8>>,
+    addresses: Addresses,
+}
+
+impl Replay {
+    /// Replays the incoming frames among `records`, e.g. read with a [`PcapReader`].
+    pub fn new(records: impl IntoIterator<Item = Record>) -> Self {
+        Replay {
+            records: records.into_iter().collect(),
+            timing: false,
+            start: None,
+            sent: Vec::new(),
+            addresses: Addresses::of_host(1),
+        }
+    }
+
+    /// Replays the pcap or pcapng file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let records = PcapReader::open(path)?.collect::<io::Result<Vec<_>>>()?;
+        Ok(Self::new(records))
+    }
+
+    /// Whether frames keep the spacing in time they were captured with, counting from the first
+    /// received frame. By default, every frame can be received right away.
+    #[inline]
+    pub fn timing(mut self, enabled: bool) -> Self {
+        self.timing = enabled;
+        self
+    }
+
+    /// The frames sent so far.
+    #[inline]
+    pub fn sent(&self) -> &[Vec<u8>] {
+        &self.sent
+    }
+
+    /// Returns the frames sent so far and forgets them.
+    #[inline]
+    pub fn take_sent(&mut self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut self.sent)
+    }
+
+    /// The number of frames that have not been received yet.
+    pub fn remaining(&self) -> usize {
+        self.records
+            .iter()
+            .filter(|record| !self.is_outgoing(record))
+            .count()
+    }
+
+    // Whether the recording host sent `record`.
+    fn is_outgoing(&self, record: &Record) -> bool {
+        match record.direction {
+            Some(direction) => direction == Direction::Outgoing,
+            None => {
+                record.link_type == LinkType::Ethernet
+                    && record.data.get(6..12) == Some(&self.addresses.hwaddr.octets()[..])
+            }
+        }
+    }
+
+    // When the next frame may be received, if it has to wait at all.
+    fn due(&mut self) -> Option<Instant> {
+        let record = self.records.front()?;
+        if !self.timing {
+            return None;
+        }
+        let (started, captured) = *self
+            .start
+            .get_or_insert_with(|| (Instant::now(), record.timestamp));
+        let since_first = record
+            .timestamp
+            .duration_since(captured)
+            .unwrap_or_default();
+        Some(started + since_first)
+    }
+}
+
+address_setters!(
+    Replay,
+    "Set it to the address of the recording host, so that the Ethernet frames it sent are skipped."
+);
+
+impl PacketIo for Replay {
+    fn recv(
+        &mut self,
+        destination: &mut [u8],
+        timeout: Option<Duration>,
+    ) -> Result<ReadOutcome, Error> {
+        while self
+            .records
+            .front()
+            .is_some_and(|record| self.is_outgoing(record))
+        {
+            self.records.pop_front();
+        }
+        if self.records.is_empty() {
+            return match timeout {
+                Some(_) => Ok(ReadOutcome::TimedOut),
+                None => Err(Error::Os {
+                    operation: Operation::Read,
+                    errno: Errno::ENODATA,
+                    context: Some("the replayed capture is exhausted".to_string()),
+                }),
+            };
+        }
+        if let Some(due) = self.due() {
+            let wait = due.saturating_duration_since(Instant::now());
+            if timeout.is_some_and(|timeout| timeout < wait) {
+                thread::sleep(timeout.unwrap());
+                return Ok(ReadOutcome::TimedOut);
+            }
+            thread::sleep(wait);
+        }
+        let record = self.records.pop_front().unwrap();
+        let frame = record
+            .data
+            .get(record.link_type.cooked_header_len()..)
+            .unwrap_or_default();
+        let len = frame.len().min(destination.len());
+        destination[..len].copy_from_slice(&frame[..len]);
+        Ok(ReadOutcome::Frame(len))
+    }
+
+    fn send(&mut self, frame: &[u8]) -> Result<usize, Error> {
+        self.sent.push(frame.to_vec());
+        Ok(frame.len())
+    }
+
+    #[inline]
+    fn get_hwaddr(&self) -> Result<MacAddr, Error> {
+        Ok(self.addresses.hwaddr)
+    }
+
+    #[inline]
+    fn get_ipaddr(&self) -> Ipv4Addr {
+        self.addresses.ipaddr
+    }
+
+    #[inline]
+    fn get_ip6addr(&self) -> Ipv6Addr {
+        self.addresses.ip6addr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet_io::recv_frame;
+    use crate::pcap::PcapWriter;
+    use std::time::UNIX_EPOCH;
+
+    fn record(millis: u64, data: &[u8], direction: Option<Direction>) -> Record {
+        Record {
+            timestamp: UNIX_EPOCH + Duration::from_millis(millis),
+            link_type: LinkType::Ethernet,
+            data: data.to_vec(),
+            direction,
+        }
+    }
+
+    #[test]
+    fn replays_incoming_frames() {
+        let mut cooked = vec![0; 16];
+        cooked.extend([0x60, 1, 2]);
+        let mut replay = Replay::new([
+            record(0, &[1], Some(Direction::Incoming)),
+            record(1, &[2], Some(Direction::Outgoing)),
+            record(2, &[3], None),
+            Record {
+                link_type: LinkType::LinuxSll,
+                ..record(3, &cooked, None)
+            },
+        ]);
+        assert_eq!(replay.remaining(), 3);
+        assert_eq!(recv_frame(&mut replay, None).unwrap(), [1]);
+        assert_eq!(recv_frame(&mut replay, None).unwrap(), [3]);
+        assert_eq!(recv_frame(&mut replay, None).unwrap(), [0x60, 1, 2]);
+        assert_eq!(replay.remaining(), 0);
+
+        assert_eq!(recv_frame(&mut replay, Some(Duration::from_secs(60))), None);
+        let err = replay.recv(&mut [0; 64], None).unwrap_err();
+        assert_eq!(err.errno(), Some(Errno::ENODATA));
+
+        replay.send(&[4, 5]).unwrap();
+        assert_eq!(replay.take_sent(), [vec![4, 5]]);
+        assert!(replay.sent().is_empty());
+    }
+
+    #[test]
+    fn skips_ethernet_frames_from_its_own_address() {
+        let host = MacAddr([0x52, 0x54, 0, 0x12, 0x34, 0x56]);
+        let peer = MacAddr([0x52, 0x54, 0, 0x65, 0x43, 0x21]);
+        let frame = |destination: MacAddr, source: MacAddr, payload: u8| {
+            [
+                &destination.octets()[..],
+                &source.octets(),
+                &[0x88, 0xb5, payload],
+            ]
+            .concat()
+        };
+        let request = frame(host, peer, 1);
+        let reply = frame(peer, host, 2);
+        let mut writer = PcapWriter::new(Vec::new(), LinkType::Ethernet).unwrap();
+        for (frame, direction) in [
+            (&request, Direction::Incoming),
+            (&reply, Direction::Outgoing),
+            (&request, Direction::Incoming),
+        ] {
+            writer.write_frame(frame, direction).unwrap();
+        }
+        let capture = writer.into_inner();
+        let records = PcapReader::new(&capture[..])
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert!(records.iter().all(|record| record.direction.is_none()));
+
+        let mut replay = Replay::new(records);
+        assert_eq!(replay.remaining(), 3);
+        replay.set_hwaddr(host);
+        assert_eq!(replay.remaining(), 2);
+        assert_eq!(recv_frame(&mut replay, None).unwrap(), request);
+        assert_eq!(recv_frame(&mut replay, None).unwrap(), request);
+        assert_eq!(recv_frame(&mut replay, Some(Duration::ZERO)), None);
+    }
+
+    #[test]
+    fn timing_keeps_the_spacing_of_the_capture() {
+        let records = [
+            record(10_000, &[1], None),
+            record(10_200, &[2], None),
+            record(10_200, &[3], None),
+        ];
+        let mut replay = Replay::new(records.clone()).timing(true);
+        let start = Instant::now();
+        assert_eq!(recv_frame(&mut replay, None).unwrap(), [1]);
+        assert!(start.elapsed() < Duration::from_millis(100));
+        assert_eq!(
+            recv_frame(&mut replay, Some(Duration::from_millis(20))),
+            None
+        );
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert_eq!(recv_frame(&mut replay, None).unwrap(), [2]);
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert_eq!(recv_frame(&mut replay, Some(Duration::ZERO)).unwrap(), [3]);
+
+        let mut replay = Replay::new(records);
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert!(recv_frame(&mut replay, Some(Duration::ZERO)).is_some());
+        }
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+}